@@ -0,0 +1,328 @@
+use crate::engine as GameEngine;
+use crate::engine::{Board, Move, MOVES};
+use crate::expectimax::{Expectimax, ExpectimaxMultithread};
+use crate::monte_carlo::{Mcts, MonteCarlo};
+use crate::ntuple::NTupleNetwork;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::error::Error;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Names accepted by `create`. Its `ntuple` agent is untrained; `NTupleAgent::load` reads the
+/// weights `train_ntuple` learned.
+pub const AGENT_NAMES: [&str; 8] = [
+    "expectimax",
+    "expectimax-mt",
+    "random",
+    "greedy",
+    "corner",
+    "montecarlo",
+    "mcts",
+    "ntuple",
+];
+
+/// A strategy that chooses moves, so players, simulations and analysis can use any of them.
+pub trait Agent: Send {
+    fn name(&self) -> &str;
+
+    /// Score of each move in `[Up, Down, Left, Right]` order, higher is better.
+    /// Invalid moves are `None`.
+    fn score_moves(&mut self, board: Board) -> [Option<f64>; 4];
+
+    /// The move to play, `None` once the game is over.
+    fn choose(&mut self, board: Board) -> Option<Move> {
+        best_move(&self.score_moves(board))
+    }
+
+    /// The move to play given the scores this agent just gave the board, so a position that was
+    /// scored for display is not searched again.
+    fn choose_from_scores(&mut self, scores: &[Option<f64>; 4]) -> Option<Move> {
+        best_move(scores)
+    }
+}
+
+/// Creates an agent with default settings from one of `AGENT_NAMES`.
+pub fn create(name: &str) -> Option<Box<dyn Agent>> {
+    let agent: Box<dyn Agent> = match name {
+        "expectimax" => Box::new(Expectimax::new()),
+        "expectimax-mt" => Box::new(ExpectimaxMultithread::new()),
+        "random" => Box::new(RandomAgent::new()),
+        "greedy" => Box::new(GreedyAgent),
+        "corner" => Box::new(CornerAgent),
+        "montecarlo" => Box::new(MonteCarlo::new(100)),
+        "mcts" => Box::new(Mcts::new(1000)),
+        "ntuple" => Box::new(NTupleAgent::new(NTupleNetwork::default())),
+        _ => return None,
+    };
+    Some(agent)
+}
+
+/// Like `create`, with the agents that draw random numbers drawing them from `seed`.
+pub fn create_seeded(name: &str, seed: u64) -> Option<Box<dyn Agent>> {
+    let agent: Box<dyn Agent> = match name {
+        "random" => Box::new(RandomAgent::with_seed(seed)),
+        "montecarlo" => Box::new(MonteCarlo::with_seed(100, seed)),
+        "mcts" => Box::new(Mcts::with_seed(1000, seed)),
+        _ => return create(name),
+    };
+    Some(agent)
+}
+
+/// The highest scoring valid move, preferring the earliest on ties.
+pub fn best_move(scores: &[Option<f64>; 4]) -> Option<Move> {
+    let mut best: Option<(Move, f64)> = None;
+    for (&direction, score) in MOVES.iter().zip(scores.iter()) {
+        if let Some(score) = *score {
+            if best.is_none_or(|(_, best_score)| score > best_score) {
+                best = Some((direction, score));
+            }
+        }
+    }
+    best.map(|(direction, _)| direction)
+}
+
+impl Agent for Expectimax {
+    fn name(&self) -> &str {
+        "expectimax"
+    }
+
+    fn score_moves(&mut self, board: Board) -> [Option<f64>; 4] {
+        Expectimax::score_moves(self, board)
+    }
+
+    fn choose(&mut self, board: Board) -> Option<Move> {
+        self.get_next_move(board)
+    }
+}
+
+impl Agent for ExpectimaxMultithread {
+    fn name(&self) -> &str {
+        "expectimax-mt"
+    }
+
+    fn score_moves(&mut self, board: Board) -> [Option<f64>; 4] {
+        ExpectimaxMultithread::score_moves(self, board)
+    }
+
+    fn choose(&mut self, board: Board) -> Option<Move> {
+        self.get_next_move(board)
+    }
+}
+
+impl Agent for MonteCarlo {
+    fn name(&self) -> &str {
+        "montecarlo"
+    }
+
+    fn score_moves(&mut self, board: Board) -> [Option<f64>; 4] {
+        MonteCarlo::score_moves(self, board)
+    }
+}
+
+impl Agent for Mcts {
+    fn name(&self) -> &str {
+        "mcts"
+    }
+
+    fn score_moves(&mut self, board: Board) -> [Option<f64>; 4] {
+        Mcts::score_moves(self, board)
+    }
+
+    fn choose(&mut self, board: Board) -> Option<Move> {
+        self.get_next_move(board)
+    }
+}
+
+/// Plays the move with the best reward plus learned afterstate value. Clones share the network,
+/// so many games can be played with one copy of its weights.
+#[derive(Clone)]
+pub struct NTupleAgent {
+    network: Arc<NTupleNetwork>,
+}
+
+impl NTupleAgent {
+    pub fn new(network: NTupleNetwork) -> Self {
+        NTupleAgent {
+            network: Arc::new(network),
+        }
+    }
+
+    /// Loads the weights `train_ntuple` saved at `path`.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn Error>> {
+        let path = path.as_ref();
+        let network = NTupleNetwork::load(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        Ok(Self::new(network))
+    }
+}
+
+impl Agent for NTupleAgent {
+    fn name(&self) -> &str {
+        "ntuple"
+    }
+
+    fn score_moves(&mut self, board: Board) -> [Option<f64>; 4] {
+        let current_score = GameEngine::get_score(board);
+        valid_moves(board, |afterstate| {
+            (GameEngine::get_score(afterstate) - current_score) as f64 + self.network.value(afterstate)
+        })
+    }
+}
+
+/// Plays any valid move with equal probability.
+pub struct RandomAgent {
+    rng: StdRng,
+}
+
+impl RandomAgent {
+    pub fn new() -> Self {
+        RandomAgent {
+            rng: StdRng::from_rng(rand::thread_rng()).unwrap(),
+        }
+    }
+
+    pub fn with_seed(seed: u64) -> Self {
+        RandomAgent {
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+}
+
+impl Default for RandomAgent {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Agent for RandomAgent {
+    fn name(&self) -> &str {
+        "random"
+    }
+
+    fn score_moves(&mut self, board: Board) -> [Option<f64>; 4] {
+        valid_moves(board, |_| 0.)
+    }
+
+    fn choose(&mut self, board: Board) -> Option<Move> {
+        let scores = self.score_moves(board);
+        self.choose_from_scores(&scores)
+    }
+
+    fn choose_from_scores(&mut self, scores: &[Option<f64>; 4]) -> Option<Move> {
+        let valid: Vec<Move> = MOVES
+            .iter()
+            .zip(scores.iter())
+            .filter(|(_, score)| score.is_some())
+            .map(|(&direction, _)| direction)
+            .collect();
+        if valid.is_empty() {
+            None
+        } else {
+            Some(valid[self.rng.gen_range(0, valid.len())])
+        }
+    }
+}
+
+/// Plays the move that scores the most points right now, looking no further ahead.
+pub struct GreedyAgent;
+
+impl Agent for GreedyAgent {
+    fn name(&self) -> &str {
+        "greedy"
+    }
+
+    fn score_moves(&mut self, board: Board) -> [Option<f64>; 4] {
+        let current_score = GameEngine::get_score(board);
+        valid_moves(board, |afterstate| (GameEngine::get_score(afterstate) - current_score) as f64)
+    }
+}
+
+/// The classic human strategy of keeping the big tiles in the bottom left corner:
+/// Down, then Left, then Right, and Up only when nothing else is possible.
+pub struct CornerAgent;
+
+impl Agent for CornerAgent {
+    fn name(&self) -> &str {
+        "corner"
+    }
+
+    fn score_moves(&mut self, board: Board) -> [Option<f64>; 4] {
+        let mut scores = valid_moves(board, |_| 0.);
+        // Preference order as scores for [Up, Down, Left, Right]
+        for (score, &preference) in scores.iter_mut().zip([1., 4., 3., 2.].iter()) {
+            if score.is_some() {
+                *score = Some(preference);
+            }
+        }
+        scores
+    }
+}
+
+// Scores the afterstate of every move that changes the board
+fn valid_moves<F: FnMut(Board) -> f64>(board: Board, mut score: F) -> [Option<f64>; 4] {
+    let mut scores = [None; 4];
+    for (slot, &direction) in scores.iter_mut().zip(MOVES.iter()) {
+        let afterstate = GameEngine::shift(board, direction);
+        if afterstate != board {
+            *slot = Some(score(afterstate));
+        }
+    }
+    scores
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_creates_every_named_agent() {
+        GameEngine::new();
+        for &name in AGENT_NAMES.iter() {
+            let agent = create(name).unwrap();
+            assert_eq!(agent.name(), name);
+        }
+        assert!(create("unknown").is_none());
+    }
+
+    #[test]
+    fn it_only_chooses_valid_moves() {
+        GameEngine::new();
+        // Only Up and Right change this board
+        let board = 0x0000000000001200;
+        let mut agents: Vec<Box<dyn Agent>> = vec![
+            Box::new(RandomAgent::with_seed(7)),
+            Box::new(GreedyAgent),
+            Box::new(CornerAgent),
+            Box::new(MonteCarlo::with_seed(5, 7)),
+        ];
+        for agent in agents.iter_mut() {
+            for _ in 0..5 {
+                match agent.choose(board) {
+                    Some(Move::Up) | Some(Move::Right) => {}
+                    other => panic!("{} chose {:?}", agent.name(), other),
+                }
+            }
+            assert!(agent.choose(0x1234432112344321).is_none());
+        }
+    }
+
+    #[test]
+    fn it_chooses_from_scores_without_searching_again() {
+        let scores = [None, Some(3.), None, Some(5.)];
+        let mut random = RandomAgent::with_seed(7);
+        for _ in 0..10 {
+            assert!(matches!(random.choose_from_scores(&scores), Some(Move::Down) | Some(Move::Right)));
+        }
+        assert!(matches!(GreedyAgent.choose_from_scores(&scores), Some(Move::Right)));
+        assert!(GreedyAgent.choose_from_scores(&[None; 4]).is_none());
+    }
+
+    #[test]
+    fn it_prefers_merges_when_greedy() {
+        GameEngine::new();
+        // Left or Right merge the pair of 4s, Up and Down merge nothing
+        let board = 0x2200000000000001;
+        assert_eq!(GreedyAgent.score_moves(board)[2], Some(8.));
+        assert!(matches!(GreedyAgent.choose(board), Some(Move::Left)));
+    }
+}
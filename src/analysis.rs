@@ -1,50 +1,29 @@
-use std::fs::File;
+use ai_2048::annotation;
+use ai_2048::data_dir::{DataDir, DATA_DIR_ENV};
+#[cfg(feature = "sqlite")]
+use ai_2048::database::Database;
+use ai_2048::engine as GameEngine;
+use ai_2048::expectimax::Expectimax;
+use ai_2048::move_quality::MoveGrade;
+use ai_2048::player_data::SessionRecord;
+use ai_2048::store::{FileStore, SessionStore};
+use ai_2048::puzzle::{self, PuzzleMiner};
+use ai_2048::schema::{self, GameMove};
+use ai_2048::validation;
+use ai_2048::web_data;
 use std::error::Error;
-use csv::Reader;
-use serde::Deserialize;
-
-#[derive(Debug, Deserialize)]
-struct MoveRecord {
-    timestamp: String,
-    board_state: String,
-    move_chosen: String,
-    time_taken_ms: u64,
-    up_score: f64,
-    down_score: f64,
-    left_score: f64,
-    right_score: f64,
-    best_score: f64,
-    chosen_score: f64,
-    variation_score: f64,
-    is_bad_move: bool,
-    game_score: u64,
-    move_number: u32,
-}
 
-#[derive(Debug, Deserialize)]
-struct SessionRecord {
-    session_id: String,
-    start_time: String,
-    end_time: String,
-    final_score: u64,
-    highest_tile: u64,
-    total_moves: u32,
-    bad_moves: u32,
-    average_time_per_move_ms: f64,
+pub fn analyze_player_data(moves_file: &str) -> Result<(), Box<dyn Error>> {
+    let moves: Vec<GameMove> = schema::read_moves(moves_file)?;
+    analyze_moves(&moves, moves_file);
+    Ok(())
 }
 
-pub fn analyze_player_data(moves_file: &str) -> Result<(), Box<dyn Error>> {
-    let mut reader = Reader::from_path(moves_file)?;
-    let mut moves: Vec<MoveRecord> = Vec::new();
-    
-    for result in reader.deserialize() {
-        let record: MoveRecord = result?;
-        moves.push(record);
-    }
-    
+/// Prints the timing, decision quality and habits of the player who made `moves`.
+pub fn analyze_moves(moves: &[GameMove], source: &str) {
     if moves.is_empty() {
-        println!("No move data found in {}", moves_file);
-        return Ok(());
+        println!("No move data found in {}", source);
+        return;
     }
     
     println!("=== Player Performance Analysis ===");
@@ -68,20 +47,48 @@ pub fn analyze_player_data(moves_file: &str) -> Result<(), Box<dyn Error>> {
     println!("\n--- Decision Quality ---");
     println!("Bad moves: {} ({:.1}%)", bad_moves, bad_move_percentage);
     
-    let avg_score_difference: f64 = moves.iter()
-        .map(|m| m.best_score - m.chosen_score)
-        .sum::<f64>() / moves.len() as f64;
-    
-    println!("Average score loss per move: {:.1}", avg_score_difference);
-    
-    // Difficulty analysis
-    let avg_variation: f64 = moves.iter().map(|m| m.variation_score).sum::<f64>() / moves.len() as f64;
-    println!("Average decision difficulty: {:.1}", avg_variation);
+    // Web recordings carry no AI scores
+    let scored: Vec<&GameMove> = moves.iter().filter(|m| m.chosen_score().is_some()).collect();
+    if !scored.is_empty() {
+        let avg_score_difference: f64 = scored.iter()
+            .map(|m| m.best_score().unwrap() - m.chosen_score().unwrap())
+            .sum::<f64>() / scored.len() as f64;
+        println!("Average score loss per move: {:.1}", avg_score_difference);
+
+        // Difficulty analysis
+        let avg_variation: f64 = scored.iter().map(|m| score_variation(&m.scores())).sum::<f64>() / scored.len() as f64;
+        println!("Average score variation: {:.1}", avg_variation);
+    }
+
+    let mut difficulty_counts = std::collections::BTreeMap::new();
+    for mv in moves.iter().filter(|m| m.difficulty.is_some()) {
+        let entry = difficulty_counts.entry(mv.difficulty.unwrap()).or_insert((0, 0));
+        entry.0 += 1;
+        if mv.is_bad_move {
+            entry.1 += 1;
+        }
+    }
+    if !difficulty_counts.is_empty() {
+        println!("\n--- Decisions by Difficulty ---");
+        for (level, (count, bad)) in difficulty_counts {
+            println!("{}: {} moves, {} bad ({:.1}%)", level, count, bad,
+                     bad as f64 / count as f64 * 100.0);
+        }
+    }
+
+    let graded: Vec<MoveGrade> = moves.iter().filter_map(|m| m.move_grade).collect();
+    if !graded.is_empty() {
+        println!("\n--- Move Quality ---");
+        for grade in [MoveGrade::Best, MoveGrade::Good, MoveGrade::Inaccuracy, MoveGrade::Mistake, MoveGrade::Blunder].iter() {
+            let count = graded.iter().filter(|&g| g == grade).count();
+            println!("{}: {} ({:.1}%)", grade, count, count as f64 / graded.len() as f64 * 100.0);
+        }
+    }
     
     // Move preference analysis
     let mut move_counts = std::collections::HashMap::new();
-    for mv in &moves {
-        *move_counts.entry(mv.move_chosen.clone()).or_insert(0) += 1;
+    for mv in moves {
+        *move_counts.entry(format!("{:?}", mv.direction)).or_insert(0) += 1;
     }
     
     println!("\n--- Move Preferences ---");
@@ -98,8 +105,8 @@ pub fn analyze_player_data(moves_file: &str) -> Result<(), Box<dyn Error>> {
     let early_bad_moves = early_game.iter().filter(|m| m.is_bad_move).count();
     let late_bad_moves = late_game.iter().filter(|m| m.is_bad_move).count();
     
-    let early_avg_time: f64 = early_game.iter().map(|m| m.time_taken_ms).sum::<f64>() / early_game.len() as f64;
-    let late_avg_time: f64 = late_game.iter().map(|m| m.time_taken_ms).sum::<f64>() / late_game.len() as f64;
+    let early_avg_time: f64 = early_game.iter().map(|m| m.time_taken_ms as f64).sum::<f64>() / early_game.len() as f64;
+    let late_avg_time: f64 = late_game.iter().map(|m| m.time_taken_ms as f64).sum::<f64>() / late_game.len() as f64;
     
     println!("\n--- Game Progression ---");
     println!("Early game bad moves: {}/{} ({:.1}%)", 
@@ -110,26 +117,19 @@ pub fn analyze_player_data(moves_file: &str) -> Result<(), Box<dyn Error>> {
              (late_bad_moves as f64 / late_game.len() as f64) * 100.0);
     println!("Early game avg time: {:.2}s", early_avg_time / 1000.0);
     println!("Late game avg time: {:.2}s", late_avg_time / 1000.0);
-    
-    Ok(())
 }
 
-pub fn analyze_sessions() -> Result<(), Box<dyn Error>> {
-    let sessions_file = "player_sessions.csv";
-    
-    if !std::path::Path::new(sessions_file).exists() {
-        println!("No sessions file found: {}", sessions_file);
-        return Ok(());
-    }
-    
-    let mut reader = Reader::from_path(sessions_file)?;
-    let mut sessions: Vec<SessionRecord> = Vec::new();
-    
-    for result in reader.deserialize() {
-        let record: SessionRecord = result?;
-        sessions.push(record);
-    }
-    
+// Standard deviation of the valid moves' scores
+fn score_variation(scores: &[Option<f64>; 4]) -> f64 {
+    let valid: Vec<f64> = scores.iter().flatten().copied().collect();
+    let count = valid.len().max(1) as f64;
+    let mean = valid.iter().sum::<f64>() / count;
+    (valid.iter().map(|score| (score - mean).powi(2)).sum::<f64>() / count).sqrt()
+}
+
+/// Summarises the sessions in `store`, from the CLI and the web server alike.
+pub fn analyze_sessions(store: &dyn SessionStore) -> Result<(), Box<dyn Error>> {
+    let sessions = store.list_sessions()?;
     if sessions.is_empty() {
         println!("No session data found");
         return Ok(());
@@ -149,26 +149,447 @@ pub fn analyze_sessions() -> Result<(), Box<dyn Error>> {
     let avg_moves: f64 = sessions.iter().map(|s| s.total_moves).sum::<u32>() as f64 / sessions.len() as f64;
     println!("Average moves per game: {:.1}", avg_moves);
     
-    let avg_bad_move_rate: f64 = sessions.iter()
-        .map(|s| s.bad_moves as f64 / s.total_moves as f64 * 100.0)
-        .sum::<f64>() / sessions.len() as f64;
-    println!("Average bad move rate: {:.1}%", avg_bad_move_rate);
+    let graded: Vec<&SessionRecord> = sessions.iter().filter(|s| s.bad_moves.is_some()).collect();
+    if !graded.is_empty() {
+        let avg_bad_move_rate: f64 = graded.iter()
+            .map(|s| s.bad_moves.unwrap_or_default() as f64 / s.total_moves.max(1) as f64 * 100.0)
+            .sum::<f64>() / graded.len() as f64;
+        println!("Average bad move rate: {:.1}% (over {} graded sessions)", avg_bad_move_rate, graded.len());
+    }
+
+    let mut players = std::collections::BTreeMap::new();
+    for session in &sessions {
+        let player = session.player_name.clone().filter(|name| !name.is_empty())
+            .unwrap_or_else(|| String::from("(unnamed)"));
+        let mode = session.game_mode.clone().filter(|mode| !mode.is_empty())
+            .unwrap_or_else(|| String::from("normal"));
+        let entry = players.entry((player, mode)).or_insert((0, 0));
+        entry.0 += 1;
+        entry.1 = entry.1.max(session.final_score);
+    }
+    println!("\n--- Players ---");
+    for ((player, mode), (games, best)) in players {
+        println!("{} ({}): {} games, best score {}", player, mode, games, best);
+    }
+
+    // Unassisted play is measured by the blind sessions
+    let mut coaching = std::collections::BTreeMap::new();
+    for session in &graded {
+        let mode = session.coaching.clone().filter(|mode| !mode.is_empty())
+            .unwrap_or_else(|| String::from("full"));
+        let entry = coaching.entry(mode).or_insert((0, 0, 0, 0.0, 0));
+        entry.0 += 1;
+        entry.1 += session.total_moves;
+        entry.2 += session.bad_moves.unwrap_or_default();
+        entry.3 += session.average_time_per_move_ms.unwrap_or_default() * session.total_moves as f64;
+        entry.4 += session.hints_requested.unwrap_or_default();
+    }
+    if !coaching.is_empty() {
+        println!("\n--- Coaching ---");
+    }
+    for (mode, (games, moves, bad_moves, total_time_ms, hints)) in coaching {
+        let moves = moves.max(1);
+        println!(
+            "{}: {} games, bad move rate {:.1}%, average time {:.0}ms, {} hints",
+            mode,
+            games,
+            bad_moves as f64 / moves as f64 * 100.0,
+            total_time_ms / moves as f64,
+            hints
+        );
+    }
     
     Ok(())
 }
 
+/// Player totals worked out by the database over every recorded move.
+#[cfg(feature = "sqlite")]
+pub fn analyze_players(database: &Database) -> Result<(), Box<dyn Error>> {
+    let players = database.player_summaries()?;
+    if !players.is_empty() {
+        println!("\n--- Recorded Moves by Player ---");
+    }
+    for player in players {
+        println!(
+            "{}: {} games, {} moves, bad move rate {:.1}%, average time {:.0}ms",
+            player.player_name,
+            player.games,
+            player.moves,
+            player.bad_moves as f64 / player.moves.max(1) as f64 * 100.0,
+            player.average_time_ms
+        );
+    }
+    Ok(())
+}
+
+/// Loads recordings into the database, the whole data directory when no files are given.
+#[cfg(feature = "sqlite")]
+pub fn import(args: &[String], data_dir: &DataDir, database: Option<&mut Database>) -> Result<(), Box<dyn Error>> {
+    let database = database.ok_or("import needs --database PATH")?;
+    let mut inputs = args.to_vec();
+    if inputs.is_empty() {
+        inputs.push(data_dir.root().to_string_lossy().into_owned());
+    }
+    let files = expand_inputs(inputs, &["game_moves_", "game_session_", "player_moves_"])?;
+    let imported = database.import(&files, data_dir)?;
+    println!(
+        "Imported {} moves from {} files and {} sessions",
+        imported.moves, imported.files, imported.sessions
+    );
+    Ok(())
+}
+
+/// Annotates every move of a recorded game with the engine's opinion of it.
+pub fn annotate(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let moves_file = args.first().ok_or("Missing moves file to annotate")?;
+    let mut depth = None;
+    let mut json_file = None;
+
+    let mut idx = 1;
+    while idx < args.len() {
+        let value = args.get(idx + 1).ok_or_else(|| format!("Missing value for {}", args[idx]));
+        match args[idx].as_str() {
+            "--depth" => depth = Some(value?.parse()?),
+            "--json" => json_file = Some(value?.clone()),
+            other => return Err(format!("Unknown option: {}", other).into()),
+        }
+        idx += 2;
+    }
+
+    GameEngine::new();
+    let mut ai = match depth {
+        Some(depth) => Expectimax::new().with_max_depth(depth),
+        None => Expectimax::new(),
+    };
+    let moves = schema::read_moves(moves_file)?;
+    let annotations = annotation::annotate_game(&moves, &mut ai)?;
+
+    print!("{}", annotation::format_report(&annotations));
+    if let Some(json_file) = json_file {
+        std::fs::write(&json_file, serde_json::to_string_pretty(&annotations)?)?;
+        println!("\nAnnotations written to {}", json_file);
+    }
+    Ok(())
+}
+
+/// Scores web-recorded games again with the Rust expectimax and writes enriched CSVs. A file
+/// that cannot be rescored is reported and the rest are still rescored.
+pub fn rescore(args: &[String], data_dir: &DataDir) -> Result<(), Box<dyn Error>> {
+    let mut inputs = Vec::new();
+    let mut depth = None;
+    let mut output_dir = String::from("rescored");
+
+    let mut idx = 0;
+    while idx < args.len() {
+        if !args[idx].starts_with("--") {
+            inputs.push(args[idx].clone());
+            idx += 1;
+            continue;
+        }
+        let value = args.get(idx + 1).ok_or_else(|| format!("Missing value for {}", args[idx]));
+        match args[idx].as_str() {
+            "--depth" => depth = Some(value?.parse()?),
+            "--output-dir" => output_dir = value?.clone(),
+            other => return Err(format!("Unknown option: {}", other).into()),
+        }
+        idx += 2;
+    }
+    if inputs.is_empty() {
+        inputs.push(data_dir.root().to_string_lossy().to_string());
+    }
+
+    let mut files = expand_inputs(inputs, &["game_moves_", "game_session_"])?;
+    // A web game saved both ways is rescored once, from its CSV
+    let session_of = |file: &std::path::Path, prefix: &str| {
+        file.file_stem().and_then(|stem| stem.to_str()).and_then(|stem| stem.strip_prefix(prefix)).map(String::from)
+    };
+    let csv_sessions: std::collections::HashSet<String> =
+        files.iter().filter_map(|file| session_of(file, "game_moves_")).collect();
+    files.retain(|file| session_of(file, "game_session_").is_none_or(|session| !csv_sessions.contains(&session)));
+
+    GameEngine::new();
+    let mut ai = match depth {
+        Some(depth) => Expectimax::new().with_max_depth(depth),
+        None => Expectimax::new(),
+    };
+    std::fs::create_dir_all(&output_dir)?;
+
+    let mut failed = 0;
+    for file in files {
+        let filename = file.to_string_lossy().to_string();
+        let stem = file.file_stem().unwrap_or_default().to_string_lossy();
+        let output = std::path::Path::new(&output_dir).join(format!("rescored_{}.csv", stem));
+        let rescored = match rescore_file(&filename, &output, &mut ai) {
+            Ok(rescored) => rescored,
+            Err(e) => {
+                println!("{}: {}", filename, e);
+                failed += 1;
+                continue;
+            }
+        };
+
+        let web_bad = rescored.iter().filter(|m| m.web_bad_move).count();
+        let bad = rescored.iter().filter(|m| m.is_bad_move).count();
+        let agreed = rescored.iter().filter(|m| m.web_bad_move == m.is_bad_move).count();
+        println!(
+            "{}: {} moves, {} bad (web flagged {}), flags agree on {:.1}% -> {}",
+            filename,
+            rescored.len(),
+            bad,
+            web_bad,
+            agreed as f64 / rescored.len().max(1) as f64 * 100.0,
+            output.display()
+        );
+    }
+    if failed > 0 {
+        return Err(format!("{} files could not be rescored", failed).into());
+    }
+    Ok(())
+}
+
+fn rescore_file(
+    filename: &str,
+    output: &std::path::Path,
+    ai: &mut Expectimax,
+) -> Result<Vec<web_data::RescoredMove>, Box<dyn Error>> {
+    let moves = web_data::load_web_moves(filename)?;
+    let rescored = web_data::rescore_moves(&moves, ai)?;
+    let mut writer = csv::Writer::from_path(output)?;
+    for mv in &rescored {
+        writer.serialize(mv)?;
+    }
+    writer.flush()?;
+    Ok(rescored)
+}
+
+/// Checks that every recorded board follows from the one before it and reconstructs the tile
+/// that appeared after each move.
+pub fn validate(args: &[String], data_dir: &DataDir) -> Result<(), Box<dyn Error>> {
+    let mut inputs = Vec::new();
+    let mut spawns_file = None;
+
+    let mut idx = 0;
+    while idx < args.len() {
+        if !args[idx].starts_with("--") {
+            inputs.push(args[idx].clone());
+            idx += 1;
+            continue;
+        }
+        let value = args.get(idx + 1).ok_or_else(|| format!("Missing value for {}", args[idx]));
+        match args[idx].as_str() {
+            "--spawns" => spawns_file = Some(value?.clone()),
+            other => return Err(format!("Unknown option: {}", other).into()),
+        }
+        idx += 2;
+    }
+    if inputs.is_empty() {
+        inputs.push(data_dir.root().to_string_lossy().to_string());
+    }
+    let files = expand_inputs(inputs, &["game_moves_", "player_moves_"])?;
+
+    GameEngine::new();
+    let mut spawns = Vec::new();
+    let mut issues = 0;
+    for file in files {
+        let filename = file.to_string_lossy().to_string();
+        let moves = schema::read_moves(&filename)?;
+        let report = validation::validate_moves(&moves);
+        let records = report.spawn_records();
+        let fours = records.iter().filter(|spawn| spawn.value == 4).count();
+        println!(
+            "{}: {} games, {} moves, {} spawns reconstructed ({:.1}% fours), {}",
+            filename,
+            report.games.len(),
+            moves.len(),
+            records.len(),
+            fours as f64 / records.len().max(1) as f64 * 100.0,
+            if report.is_valid() { String::from("valid") } else { format!("{} issues", report.issues.len()) }
+        );
+        for issue in &report.issues {
+            println!("  {}", issue);
+        }
+        issues += report.issues.len();
+        spawns.extend(records);
+    }
+
+    if let Some(spawns_file) = spawns_file {
+        let mut writer = csv::Writer::from_path(&spawns_file)?;
+        for spawn in &spawns {
+            writer.serialize(spawn)?;
+        }
+        writer.flush()?;
+        println!("Spawn sequence written to {}", spawns_file);
+    }
+    if issues > 0 {
+        return Err(format!("{} impossible or missing transitions found", issues).into());
+    }
+    Ok(())
+}
+
+/// Mines puzzles from recorded games: positions where expectimax rates one move clearly above
+/// the rest.
+pub fn puzzles(args: &[String], data_dir: &DataDir) -> Result<(), Box<dyn Error>> {
+    let mut inputs = Vec::new();
+    let mut depth = None;
+    let mut miner = PuzzleMiner::new();
+    let mut output = String::from("puzzles.csv");
+
+    let mut idx = 0;
+    while idx < args.len() {
+        if !args[idx].starts_with("--") {
+            inputs.push(args[idx].clone());
+            idx += 1;
+            continue;
+        }
+        if args[idx] == "--mistakes-only" {
+            miner = miner.with_mistakes_only(true);
+            idx += 1;
+            continue;
+        }
+        let value = args.get(idx + 1).ok_or_else(|| format!("Missing value for {}", args[idx]));
+        match args[idx].as_str() {
+            "--depth" => depth = Some(value?.parse()?),
+            "--min-gap" => miner = miner.with_min_gap(value?.parse()?),
+            "--output" => output = value?.clone(),
+            other => return Err(format!("Unknown option: {}", other).into()),
+        }
+        idx += 2;
+    }
+    if inputs.is_empty() {
+        inputs.push(data_dir.root().to_string_lossy().to_string());
+    }
+    let files = expand_inputs(inputs, &["game_moves_", "player_moves_"])?;
+
+    GameEngine::new();
+    let mut ai = match depth {
+        Some(depth) => Expectimax::new().with_max_depth(depth),
+        None => Expectimax::new(),
+    };
+    let mut found = Vec::new();
+    for file in files {
+        let filename = file.to_string_lossy().to_string();
+        let moves = schema::read_moves(&filename)?;
+        let mined = miner.mine(&moves, &mut ai);
+        let mistakes = mined.iter().filter(|p| p.played != Some(p.best_move)).count();
+        println!(
+            "{}: {} positions, {} puzzles ({} missed by the player)",
+            filename,
+            moves.len(),
+            mined.len(),
+            mistakes
+        );
+        found.extend(mined);
+    }
+
+    puzzle::save_puzzles(&output, &found)?;
+    println!("{} puzzles written to {}", found.len(), output);
+    Ok(())
+}
+
+// Directories stand for the moves files inside them whose names start with one of `prefixes`
+fn expand_inputs(inputs: Vec<String>, prefixes: &[&str]) -> Result<Vec<std::path::PathBuf>, Box<dyn Error>> {
+    let mut files = Vec::new();
+    for input in inputs {
+        let path = std::path::Path::new(&input);
+        if path.is_dir() {
+            let mut found: Vec<_> = std::fs::read_dir(path)?
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|path| {
+                    let name = path.file_name().unwrap_or_default().to_string_lossy();
+                    prefixes.iter().any(|prefix| name.starts_with(prefix))
+                        && (name.ends_with(".csv") || name.ends_with(".json"))
+                })
+                .collect();
+            found.sort();
+            files.extend(found);
+        } else {
+            files.push(path.to_path_buf());
+        }
+    }
+    Ok(files)
+}
+
+// Takes `name VALUE` out of `args`, for options that apply to every subcommand
+fn take_global_option(args: &mut Vec<String>, name: &str) -> Result<Option<String>, Box<dyn Error>> {
+    match args.iter().position(|arg| arg == name) {
+        Some(idx) if idx + 1 < args.len() => {
+            let value = args.remove(idx + 1);
+            args.remove(idx);
+            Ok(Some(value))
+        }
+        Some(_) => Err(format!("Missing value for {}", name).into()),
+        None => Ok(None),
+    }
+}
+
 fn main() -> Result<(), Box<dyn Error>> {
-    let args: Vec<String> = std::env::args().collect();
+    let mut args: Vec<String> = std::env::args().collect();
+    let flag = take_global_option(&mut args, "--data-dir")?;
+    let data_dir = DataDir::resolve(flag.as_deref());
+    let database = take_global_option(&mut args, "--database")?;
+    #[cfg(not(feature = "sqlite"))]
+    if database.is_some() {
+        return Err("--database needs a build with the sqlite feature (cargo run --features sqlite)".into());
+    }
+    #[cfg(feature = "sqlite")]
+    let mut database = database.map(Database::open).transpose()?;
     
+    match args.get(1).map(String::as_str) {
+        Some("annotate") => return annotate(&args[2..]),
+        Some("rescore") => return rescore(&args[2..], &data_dir),
+        Some("validate") => return validate(&args[2..], &data_dir),
+        Some("puzzles") => return puzzles(&args[2..], &data_dir),
+        #[cfg(feature = "sqlite")]
+        Some("import") => return import(&args[2..], &data_dir, database.as_mut()),
+        #[cfg(not(feature = "sqlite"))]
+        Some("import") => return Err("import needs a build with the sqlite feature (cargo run --features sqlite)".into()),
+        _ => {}
+    }
+
+    // Sessions come from the database when there is one, else from the data directory
+    let files = FileStore::new(data_dir.clone());
+    #[cfg(feature = "sqlite")]
+    let store: &dyn SessionStore = match &database {
+        Some(database) => database,
+        None => &files,
+    };
+    #[cfg(not(feature = "sqlite"))]
+    let store: &dyn SessionStore = &files;
+
     if args.len() > 1 {
-        println!("Analyzing moves file: {}", &args[1]);
-        analyze_player_data(&args[1])?;
+        // A bare file name is looked for in the data directory too
+        let mut moves_file = std::path::PathBuf::from(&args[1]);
+        if !moves_file.exists() && data_dir.root().join(&moves_file).exists() {
+            moves_file = data_dir.root().join(&moves_file);
+        }
+        // and anything that is not a file is taken for a recorded session
+        match store.load_session(&args[1])? {
+            Some(stored) if !moves_file.exists() => {
+                println!("Analyzing session: {}", args[1]);
+                analyze_moves(&stored.moves, &args[1]);
+            }
+            _ => {
+                println!("Analyzing moves file: {}", moves_file.display());
+                analyze_player_data(&moves_file.to_string_lossy())?;
+            }
+        }
     } else {
-        println!("Usage: cargo run --bin analyze [moves_file.csv]");
-        println!("If no file specified, will analyze session summary only.\n");
+        println!("Usage: cargo run --bin analyze [--data-dir DIR] [--database PATH] [moves_file.csv | session_id]");
+        println!("       cargo run --bin analyze annotate moves_file.csv [--depth N] [--json PATH]");
+        println!("       cargo run --bin analyze rescore [FILE_OR_DIR...] [--depth N] [--output-dir DIR]");
+        println!("       cargo run --bin analyze validate [FILE_OR_DIR...] [--spawns PATH]");
+        println!("       cargo run --bin analyze puzzles [FILE_OR_DIR...] [--depth N] [--min-gap X] [--mistakes-only] [--output PATH]");
+        println!("       cargo run --bin analyze --database PATH import [FILE_OR_DIR...]");
+        println!("The data directory (--data-dir, ${} or the current directory) is read when no", DATA_DIR_ENV);
+        println!("files are given; without a moves file only the session summaries are analyzed.");
+        println!("With --database (sqlite feature) sessions come from the database instead.\n");
     }
     
-    analyze_sessions()?;
+    analyze_sessions(store)?;
+    #[cfg(feature = "sqlite")]
+    if let Some(database) = &database {
+        analyze_players(database)?;
+    }
     
     Ok(())
 }
@@ -0,0 +1,200 @@
+use crate::agent::{best_move, Agent};
+use crate::engine::{Move, MOVES};
+use crate::move_quality::{self, MoveGrade, QualityThresholds};
+use crate::schema::GameMove;
+use serde::Serialize;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MoveAnnotation {
+    pub move_number: u32,
+    pub board_state: String,
+    pub played: Move,
+    /// The engine's move, empty when no move changes the board
+    pub engine_move: Option<Move>,
+    /// Evaluation of the engine's move, the best available before the move
+    pub eval_before: Option<f64>,
+    /// Evaluation of the move played, empty when it does not change the board
+    pub eval_after: Option<f64>,
+    /// Evaluation given up, relative to the best
+    pub loss: Option<f64>,
+    /// Grade of the move played by the engine's evaluations, empty when it does not change the
+    /// board
+    pub grade: Option<MoveGrade>,
+    /// `!`, `?`, `??` or empty
+    pub glyph: &'static str,
+}
+
+/// Annotates a game with the grading `move_quality::grade_by_scores` gives agents' scores.
+pub fn annotate_game(records: &[GameMove], ai: &mut dyn Agent) -> Result<Vec<MoveAnnotation>, String> {
+    annotate_game_with(records, ai, &QualityThresholds::for_scores())
+}
+
+/// Replays a recorded game, scoring every position with `ai` and grading each move played by
+/// those scores. Mistakes earn `?`, blunders `??`, and the engine's move earns `!` when every
+/// alternative would be a mistake or worse. A recorded move that does not change the board is
+/// left ungraded; only a board that cannot be read stops the game.
+pub fn annotate_game_with(
+    records: &[GameMove],
+    ai: &mut dyn Agent,
+    thresholds: &QualityThresholds,
+) -> Result<Vec<MoveAnnotation>, String> {
+    let mut annotations = Vec::with_capacity(records.len());
+    for record in records {
+        let board = record
+            .board()
+            .ok_or_else(|| format!("Move {}: malformed board '{}'", record.move_number, record.board_state))?;
+        let played = record.direction;
+
+        let scores = ai.score_moves(board);
+        let played_idx = MOVES.iter().position(|&direction| direction == played).unwrap();
+        let eval_after = scores[played_idx];
+        let engine_move = best_move(&scores);
+        let eval_before = scores.iter().flatten().copied().reduce(f64::max);
+        let loss = eval_before
+            .zip(eval_after)
+            .map(|(before, after)| (before - after) / before.abs().max(f64::EPSILON));
+
+        let grade = move_quality::grade_by_scores(&scores, played, thresholds);
+        let mut alternatives = MOVES
+            .iter()
+            .filter(|&&direction| direction != played)
+            .filter_map(|&direction| move_quality::grade_by_scores(&scores, direction, thresholds))
+            .peekable();
+        let only_move = alternatives.peek().is_some() && alternatives.all(|grade| grade >= MoveGrade::Mistake);
+        let glyph = match grade {
+            Some(MoveGrade::Best) if only_move => "!",
+            Some(MoveGrade::Mistake) => "?",
+            Some(MoveGrade::Blunder) => "??",
+            _ => "",
+        };
+
+        annotations.push(MoveAnnotation {
+            move_number: record.move_number,
+            board_state: record.board_state.clone(),
+            played,
+            engine_move,
+            eval_before,
+            eval_after,
+            loss,
+            grade,
+            glyph,
+        });
+    }
+    Ok(annotations)
+}
+
+/// A plain text report with one line per move and a summary.
+pub fn format_report(annotations: &[MoveAnnotation]) -> String {
+    let mut report = String::from("=== Annotated Game ===\n");
+    for annotation in annotations {
+        let played = format!("{:?}{}", annotation.played, annotation.glyph);
+        let eval_before = annotation.eval_before.map_or_else(|| String::from("-"), |eval| format!("{:.0}", eval));
+        let eval_after = match (annotation.eval_after, annotation.loss) {
+            (Some(eval), Some(loss)) => format!("{:.0} ({:+.2}%)", eval, -loss * 100.0),
+            _ => String::from("- (the move changes nothing)"),
+        };
+        let engine = match annotation.engine_move {
+            Some(engine_move) if engine_move != annotation.played => format!("engine prefers {:?}", engine_move),
+            _ => String::new(),
+        };
+        report.push_str(&format!(
+            "{:>4}. {:<8} eval {} -> {} {}\n",
+            annotation.move_number, played, eval_before, eval_after, engine
+        ));
+    }
+
+    let count = |glyph: &str| annotations.iter().filter(|a| a.glyph == glyph).count();
+    let matched = annotations.iter().filter(|a| a.engine_move == Some(a.played)).count();
+    let invalid = annotations.iter().filter(|a| a.grade.is_none()).count();
+    report.push_str("\n--- Summary ---\n");
+    report.push_str(&format!(
+        "Engine moves played: {}/{} ({:.1}%)\n",
+        matched,
+        annotations.len(),
+        matched as f64 / annotations.len().max(1) as f64 * 100.0
+    ));
+    report.push_str(&format!("Only moves found (!): {}\n", count("!")));
+    report.push_str(&format!("Mistakes (?): {}\n", count("?")));
+    report.push_str(&format!("Blunders (??): {}\n", count("??")));
+    if invalid > 0 {
+        report.push_str(&format!("Moves that change nothing: {}\n", invalid));
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::{Source, SCHEMA_VERSION};
+
+    // Scores every move by a fixed amount, so the test controls the evaluation
+    struct FixedAgent([Option<f64>; 4]);
+
+    impl Agent for FixedAgent {
+        fn name(&self) -> &str {
+            "fixed"
+        }
+
+        fn score_moves(&mut self, _board: crate::engine::Board) -> [Option<f64>; 4] {
+            self.0
+        }
+    }
+
+    fn record(move_number: u32, direction: Move) -> GameMove {
+        GameMove {
+            schema_version: SCHEMA_VERSION,
+            source: Source::Cli,
+            session_id: String::from("test"),
+            player_name: None,
+            game_mode: None,
+            move_number,
+            timestamp: String::new(),
+            board_state: String::from("1,1,0,0,0,0,0,0,0,0,0,0,0,0,0,0"),
+            direction,
+            time_taken_ms: 0,
+            game_score: 0,
+            up_score: None,
+            down_score: None,
+            left_score: None,
+            right_score: None,
+            is_bad_move: false,
+            difficulty: None,
+            move_grade: None,
+            expected_score_loss: None,
+            win_probability_loss: None,
+            tiles_added: None,
+            tiles_removed: None,
+            coaching: None,
+            hint_requested: None,
+            agent: None,
+        }
+    }
+
+    #[test]
+    fn it_marks_moves_with_glyphs() {
+        let records = vec![record(1, Move::Left), record(2, Move::Down), record(3, Move::Right)];
+        let mut ai = FixedAgent([None, Some(970.), Some(1000.), Some(900.)]);
+        let annotations = annotate_game(&records, &mut ai).unwrap();
+
+        let glyphs: Vec<&str> = annotations.iter().map(|a| a.glyph).collect();
+        assert_eq!(glyphs, vec!["!", "?", "??"]);
+        assert!(annotations.iter().all(|a| a.engine_move == Some(Move::Left)));
+        assert!((annotations[1].loss.unwrap() - 0.03).abs() < 1e-9);
+        assert_eq!(annotations[1].grade, Some(MoveGrade::Mistake));
+        assert!(format_report(&annotations).contains("Blunders (??): 1"));
+    }
+
+    #[test]
+    fn it_marks_moves_that_change_nothing_and_carries_on() {
+        let mut ai = FixedAgent([None, Some(1.), Some(1.), Some(1.)]);
+        let annotations = annotate_game(&[record(1, Move::Up), record(2, Move::Left)], &mut ai).unwrap();
+        assert_eq!(annotations.len(), 2);
+        assert_eq!((annotations[0].eval_after, annotations[0].grade, annotations[0].glyph), (None, None, ""));
+        assert_eq!(annotations[1].grade, Some(MoveGrade::Best));
+        assert!(format_report(&annotations).contains("Moves that change nothing: 1"));
+
+        let mut malformed = record(1, Move::Left);
+        malformed.board_state = String::from("1,1");
+        assert!(annotate_game(&[malformed], &mut ai).is_err());
+    }
+}
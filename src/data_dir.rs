@@ -0,0 +1,127 @@
+//! Where recordings are kept. CLI and web recordings share one directory laid out like the web
+//! server's `deploy/data`:
+//!
+//! - `game_moves_<session>.csv` and `game_session_<session>.json`: web games
+//! - `player_sessions.csv`: the web server's session summary
+//! - `player_moves_<session>.csv`: CLI games, one row per move
+//! - `player_sessions_<YYYYMM>.json`: CLI sessions by the month they started
+//! - `cli_sessions.csv`: the CLI's session summary
+//! - `player_session_<session>.incomplete.json`: a CLI game still in progress
+//!
+//! The CLI keeps its summary apart from the web server's because both append to their own
+//! file with their own columns.
+
+use chrono::{DateTime, Utc};
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Environment variable naming the data directory when no `--data-dir` is given.
+pub const DATA_DIR_ENV: &str = "AI2048_DATA_DIR";
+
+const INCOMPLETE_PREFIX: &str = "player_session_";
+const INCOMPLETE_SUFFIX: &str = ".incomplete.json";
+const SESSIONS_JSON_PREFIX: &str = "player_sessions_";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DataDir {
+    root: PathBuf,
+}
+
+impl Default for DataDir {
+    fn default() -> Self {
+        DataDir::new(".")
+    }
+}
+
+impl DataDir {
+    pub fn new<P: AsRef<Path>>(root: P) -> Self {
+        DataDir {
+            root: root.as_ref().to_path_buf(),
+        }
+    }
+
+    /// The directory given on the command line, else the one named by `AI2048_DATA_DIR`, else
+    /// the current directory.
+    pub fn resolve(flag: Option<&str>) -> Self {
+        match flag {
+            Some(root) => DataDir::new(root),
+            None => match std::env::var(DATA_DIR_ENV) {
+                Ok(root) if !root.is_empty() => DataDir::new(root),
+                _ => DataDir::default(),
+            },
+        }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn create(&self) -> io::Result<()> {
+        std::fs::create_dir_all(&self.root)
+    }
+
+    pub fn cli_moves(&self, session_id: &str) -> PathBuf {
+        self.root.join(format!("player_moves_{}.csv", session_id))
+    }
+
+    pub fn web_moves(&self, session_id: &str) -> PathBuf {
+        self.root.join(format!("game_moves_{}.csv", session_id))
+    }
+
+    pub fn cli_sessions_json(&self, start_time: DateTime<Utc>) -> PathBuf {
+        self.root.join(format!("{}{}.json", SESSIONS_JSON_PREFIX, start_time.format("%Y%m")))
+    }
+
+    /// Every month's `cli_sessions_json`, oldest first.
+    pub fn cli_sessions_jsons(&self) -> io::Result<Vec<PathBuf>> {
+        self.list(|name| name.starts_with(SESSIONS_JSON_PREFIX) && name.ends_with(".json"))
+    }
+
+    pub fn cli_summary(&self) -> PathBuf {
+        self.root.join("cli_sessions.csv")
+    }
+
+    /// The web server's summary, or the CLI's from before it had a file of its own.
+    pub fn web_summary(&self) -> PathBuf {
+        self.root.join("player_sessions.csv")
+    }
+
+    pub fn incomplete_marker(&self, session_id: &str) -> PathBuf {
+        self.root
+            .join(format!("{}{}{}", INCOMPLETE_PREFIX, session_id, INCOMPLETE_SUFFIX))
+    }
+
+    /// Markers of the CLI games that were started but never saved, oldest first.
+    pub fn incomplete_markers(&self) -> io::Result<Vec<PathBuf>> {
+        self.list(|name| name.starts_with(INCOMPLETE_PREFIX) && name.ends_with(INCOMPLETE_SUFFIX))
+    }
+
+    // Files in the directory whose names match, sorted by name
+    fn list<F: Fn(&str) -> bool>(&self, matches: F) -> io::Result<Vec<PathBuf>> {
+        if !self.root.is_dir() {
+            return Ok(Vec::new());
+        }
+        let mut files: Vec<PathBuf> = std::fs::read_dir(&self.root)?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| matches(&path.file_name().unwrap_or_default().to_string_lossy()))
+            .collect();
+        files.sort();
+        Ok(files)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_lays_out_recordings() {
+        let data = DataDir::resolve(Some("deploy/data"));
+        assert_eq!(data.cli_moves("20250801_162156_oej58"), Path::new("deploy/data/player_moves_20250801_162156_oej58.csv"));
+        assert_eq!(data.web_summary(), Path::new("deploy/data/player_sessions.csv"));
+        let start = "2025-08-01T04:21:56Z".parse().unwrap();
+        assert_eq!(data.cli_sessions_json(start), Path::new("deploy/data/player_sessions_202508.json"));
+        assert!(data.incomplete_markers().unwrap().is_empty());
+        assert!(data.cli_sessions_jsons().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,458 @@
+//! Sessions and moves in a SQLite database, for queries across many recorded games. Built with
+//! the `sqlite` feature.
+//!
+//! The tables hold the same fields as the CSV files: `moves` has a row per `GameMove`, keyed by
+//! session and move number, and `sessions` a row per `SessionRecord`. Enum columns are stored
+//! as text spelled as in the CSV files, so importing a file again replaces its rows.
+
+use crate::data_dir::DataDir;
+use crate::player_data::{self, GameSession, SessionRecord};
+use crate::schema::{self, Format, GameMove};
+use crate::store::{SessionStore, StoredSession};
+use crate::web_data;
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::error::Error;
+use std::path::Path;
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS sessions (
+        session_id TEXT PRIMARY KEY,
+        player_name TEXT,
+        game_mode TEXT,
+        start_time TEXT NOT NULL,
+        end_time TEXT,
+        final_score INTEGER NOT NULL,
+        highest_tile INTEGER NOT NULL,
+        total_moves INTEGER NOT NULL,
+        bad_moves INTEGER,
+        average_time_per_move_ms REAL,
+        game_time INTEGER,
+        coaching TEXT,
+        hints_requested INTEGER,
+        starting_board TEXT,
+        seed INTEGER,
+        complete INTEGER
+    );
+    CREATE INDEX IF NOT EXISTS sessions_by_player ON sessions (player_name, game_mode);
+    CREATE INDEX IF NOT EXISTS sessions_by_start ON sessions (start_time);
+
+    CREATE TABLE IF NOT EXISTS moves (
+        session_id TEXT NOT NULL,
+        move_number INTEGER NOT NULL,
+        schema_version INTEGER NOT NULL,
+        source TEXT NOT NULL,
+        player_name TEXT,
+        game_mode TEXT,
+        timestamp TEXT NOT NULL,
+        board_state TEXT NOT NULL,
+        direction TEXT NOT NULL,
+        time_taken_ms INTEGER NOT NULL,
+        game_score INTEGER NOT NULL,
+        up_score REAL,
+        down_score REAL,
+        left_score REAL,
+        right_score REAL,
+        is_bad_move INTEGER NOT NULL,
+        difficulty TEXT,
+        move_grade TEXT,
+        expected_score_loss REAL,
+        win_probability_loss REAL,
+        tiles_added INTEGER,
+        tiles_removed INTEGER,
+        coaching TEXT,
+        hint_requested INTEGER,
+        agent TEXT,
+        PRIMARY KEY (session_id, move_number)
+    );
+    CREATE INDEX IF NOT EXISTS moves_by_player ON moves (player_name, is_bad_move);
+    CREATE INDEX IF NOT EXISTS moves_by_grade ON moves (move_grade);
+";
+
+const MOVE_COLUMNS: &str = "session_id, move_number, schema_version, source, player_name, game_mode, timestamp, \
+    board_state, direction, time_taken_ms, game_score, up_score, down_score, left_score, right_score, is_bad_move, \
+    difficulty, move_grade, expected_score_loss, win_probability_loss, tiles_added, tiles_removed, coaching, \
+    hint_requested, agent";
+
+const SESSION_COLUMNS: &str = "session_id, player_name, game_mode, start_time, end_time, final_score, highest_tile, \
+    total_moves, bad_moves, average_time_per_move_ms, game_time, coaching, hints_requested, starting_board, seed, \
+    complete";
+
+/// Totals over every move a player made themselves, as `analyze` shows them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlayerSummary {
+    /// Empty for unnamed players
+    pub player_name: String,
+    pub games: u32,
+    pub moves: u32,
+    pub bad_moves: u32,
+    pub average_time_ms: f64,
+}
+
+/// What `Database::import` added. Sessions found in more than one file count once.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ImportSummary {
+    pub files: usize,
+    pub moves: usize,
+    pub sessions: usize,
+}
+
+pub struct Database {
+    conn: Connection,
+}
+
+impl Database {
+    /// Opens the database at `path`, creating it and its tables when missing.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn Error>> {
+        Self::with_connection(Connection::open(path)?)
+    }
+
+    pub fn open_in_memory() -> Result<Self, Box<dyn Error>> {
+        Self::with_connection(Connection::open_in_memory()?)
+    }
+
+    fn with_connection(conn: Connection) -> Result<Self, Box<dyn Error>> {
+        conn.execute_batch(SCHEMA)?;
+        // Databases created before moves kept their agent lack the column
+        if conn.prepare("SELECT agent FROM moves LIMIT 0").is_err() {
+            conn.execute_batch("ALTER TABLE moves ADD COLUMN agent TEXT")?;
+        }
+        Ok(Database { conn })
+    }
+
+    /// Adds a move, replacing any recorded before with the same session and move number.
+    pub fn insert_move(&self, mv: &GameMove) -> Result<(), Box<dyn Error>> {
+        insert_move(&self.conn, mv)
+    }
+
+    /// Adds a session, replacing any recorded before with the same ID.
+    pub fn insert_session(&self, session: &SessionRecord) -> Result<(), Box<dyn Error>> {
+        insert_session(&self.conn, session)
+    }
+
+    /// Every session, oldest first.
+    pub fn sessions(&self) -> Result<Vec<SessionRecord>, Box<dyn Error>> {
+        let mut statement = self
+            .conn
+            .prepare(&format!("SELECT {} FROM sessions ORDER BY start_time, session_id", SESSION_COLUMNS))?;
+        let sessions = statement.query_map([], session_from_row)?.collect::<Result<_, _>>()?;
+        Ok(sessions)
+    }
+
+    pub fn session(&self, session_id: &str) -> Result<Option<SessionRecord>, Box<dyn Error>> {
+        let session = self
+            .conn
+            .query_row(
+                &format!("SELECT {} FROM sessions WHERE session_id = ?1", SESSION_COLUMNS),
+                [session_id],
+                session_from_row,
+            )
+            .optional()?;
+        Ok(session)
+    }
+
+    /// The moves of one game in the order they were made.
+    pub fn moves(&self, session_id: &str) -> Result<Vec<GameMove>, Box<dyn Error>> {
+        let mut statement = self.conn.prepare(&format!(
+            "SELECT {} FROM moves WHERE session_id = ?1 ORDER BY move_number",
+            MOVE_COLUMNS
+        ))?;
+        let moves = statement.query_map([session_id], move_from_row)?.collect::<Result<_, _>>()?;
+        Ok(moves)
+    }
+
+    /// Move totals per player over every recorded game, best players by bad move rate first. Moves
+    /// an agent made in autoplay are left out.
+    pub fn player_summaries(&self) -> Result<Vec<PlayerSummary>, Box<dyn Error>> {
+        let mut statement = self.conn.prepare(
+            "SELECT COALESCE(player_name, ''), COUNT(DISTINCT session_id), COUNT(*), SUM(is_bad_move), \
+             AVG(time_taken_ms) FROM moves WHERE agent IS NULL GROUP BY COALESCE(player_name, '') \
+             ORDER BY CAST(SUM(is_bad_move) AS REAL) / COUNT(*), COALESCE(player_name, '')",
+        )?;
+        let summaries = statement
+            .query_map([], |row| {
+                Ok(PlayerSummary {
+                    player_name: row.get(0)?,
+                    games: row.get(1)?,
+                    moves: row.get(2)?,
+                    bad_moves: row.get(3)?,
+                    average_time_ms: row.get(4)?,
+                })
+            })?
+            .collect::<Result<_, _>>()?;
+        Ok(summaries)
+    }
+
+    /// Adds the moves files among `files` (as `analyze` finds them), with the session held in
+    /// each web `game_session_*.json`, and the sessions in `data_dir`: the CLI's monthly JSON
+    /// files and both session summaries. It all goes in one transaction.
+    pub fn import<P: AsRef<Path>>(&mut self, files: &[P], data_dir: &DataDir) -> Result<ImportSummary, Box<dyn Error>> {
+        let transaction = self.conn.transaction()?;
+        let mut summary = ImportSummary::default();
+        // The same session is often in several files, so they are counted once
+        let mut session_ids = HashSet::new();
+        for file in files {
+            let filename = file.as_ref().to_string_lossy();
+            let moves = schema::read_moves(&filename).map_err(|e| format!("{}: {}", filename, e))?;
+            for mv in &moves {
+                insert_move(&transaction, mv)?;
+            }
+            summary.files += 1;
+            summary.moves += moves.len();
+            if schema::detect_format(&filename)? == Format::WebJson {
+                let session = web_data::load_web_session(&filename).map_err(|e| format!("{}: {}", filename, e))?;
+                insert_session(&transaction, &session)?;
+                session_ids.insert(session.session_id);
+            }
+        }
+        let mut sessions_files = data_dir.cli_sessions_jsons()?;
+        sessions_files.extend([data_dir.cli_summary(), data_dir.web_summary()]);
+        for sessions_file in sessions_files.iter().filter(|path| path.exists()) {
+            let sessions = if sessions_file.extension().is_some_and(|ext| ext == "json") {
+                player_data::read_sessions_json(sessions_file)?
+            } else {
+                player_data::read_session_records(sessions_file)?
+            };
+            for session in sessions {
+                insert_session(&transaction, &session)?;
+                session_ids.insert(session.session_id);
+            }
+        }
+        summary.sessions = session_ids.len();
+        transaction.commit()?;
+        Ok(summary)
+    }
+}
+
+impl SessionStore for Database {
+    fn append_move(&mut self, mv: &GameMove) -> Result<(), Box<dyn Error>> {
+        self.insert_move(mv)
+    }
+
+    fn finish_session(&mut self, session: &GameSession) -> Result<(), Box<dyn Error>> {
+        self.insert_session(&SessionRecord::from(session))
+    }
+
+    fn list_sessions(&self) -> Result<Vec<SessionRecord>, Box<dyn Error>> {
+        self.sessions()
+    }
+
+    fn load_session(&self, session_id: &str) -> Result<Option<StoredSession>, Box<dyn Error>> {
+        match self.session(session_id)? {
+            Some(session) => Ok(Some(StoredSession {
+                session,
+                moves: self.moves(session_id)?,
+            })),
+            None => Ok(None),
+        }
+    }
+}
+
+fn insert_move(conn: &Connection, mv: &GameMove) -> Result<(), Box<dyn Error>> {
+    let mut statement = conn.prepare_cached(&format!(
+        "INSERT OR REPLACE INTO moves ({}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, \
+         ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22, ?23, ?24, ?25)",
+        MOVE_COLUMNS
+    ))?;
+    statement.execute(params![
+        mv.session_id,
+        mv.move_number,
+        mv.schema_version,
+        to_text(&mv.source)?,
+        mv.player_name,
+        mv.game_mode,
+        mv.timestamp,
+        mv.board_state,
+        to_text(&mv.direction)?,
+        mv.time_taken_ms,
+        mv.game_score,
+        mv.up_score,
+        mv.down_score,
+        mv.left_score,
+        mv.right_score,
+        mv.is_bad_move,
+        mv.difficulty.as_ref().map(to_text).transpose()?,
+        mv.move_grade.as_ref().map(to_text).transpose()?,
+        mv.expected_score_loss,
+        mv.win_probability_loss,
+        mv.tiles_added,
+        mv.tiles_removed,
+        mv.coaching.as_ref().map(to_text).transpose()?,
+        mv.hint_requested,
+        mv.agent,
+    ])?;
+    Ok(())
+}
+
+fn insert_session(conn: &Connection, session: &SessionRecord) -> Result<(), Box<dyn Error>> {
+    let mut statement = conn.prepare_cached(&format!(
+        "INSERT OR REPLACE INTO sessions ({}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, \
+         ?14, ?15, ?16)",
+        SESSION_COLUMNS
+    ))?;
+    statement.execute(params![
+        session.session_id,
+        session.player_name,
+        session.game_mode,
+        session.start_time,
+        session.end_time,
+        session.final_score,
+        session.highest_tile,
+        session.total_moves,
+        session.bad_moves,
+        session.average_time_per_move_ms,
+        session.game_time,
+        session.coaching,
+        session.hints_requested,
+        session.starting_board,
+        session.seed,
+        session.complete,
+    ])?;
+    Ok(())
+}
+
+// Enums are stored as the text serde gives them in CSV and JSON
+fn to_text<T: Serialize>(value: &T) -> Result<String, serde_json::Error> {
+    match serde_json::to_value(value)? {
+        serde_json::Value::String(text) => Ok(text),
+        other => Ok(other.to_string()),
+    }
+}
+
+fn from_text<T: DeserializeOwned>(row: &Row, idx: usize) -> rusqlite::Result<Option<T>> {
+    let text: Option<String> = row.get(idx)?;
+    text.map(|text| {
+        serde_json::from_value(serde_json::Value::String(text))
+            .map_err(|e| rusqlite::Error::FromSqlConversionFailure(idx, rusqlite::types::Type::Text, Box::new(e)))
+    })
+    .transpose()
+}
+
+fn required<T>(value: Option<T>, idx: usize) -> rusqlite::Result<T> {
+    value.ok_or(rusqlite::Error::InvalidColumnType(idx, String::from("NULL"), rusqlite::types::Type::Null))
+}
+
+fn move_from_row(row: &Row) -> rusqlite::Result<GameMove> {
+    Ok(GameMove {
+        session_id: row.get(0)?,
+        move_number: row.get(1)?,
+        schema_version: row.get(2)?,
+        source: required(from_text(row, 3)?, 3)?,
+        player_name: row.get(4)?,
+        game_mode: row.get(5)?,
+        timestamp: row.get(6)?,
+        board_state: row.get(7)?,
+        direction: required(from_text(row, 8)?, 8)?,
+        time_taken_ms: row.get(9)?,
+        game_score: row.get(10)?,
+        up_score: row.get(11)?,
+        down_score: row.get(12)?,
+        left_score: row.get(13)?,
+        right_score: row.get(14)?,
+        is_bad_move: row.get(15)?,
+        difficulty: from_text(row, 16)?,
+        move_grade: from_text(row, 17)?,
+        expected_score_loss: row.get(18)?,
+        win_probability_loss: row.get(19)?,
+        tiles_added: row.get(20)?,
+        tiles_removed: row.get(21)?,
+        coaching: from_text(row, 22)?,
+        hint_requested: row.get(23)?,
+        agent: row.get(24)?,
+    })
+}
+
+fn session_from_row(row: &Row) -> rusqlite::Result<SessionRecord> {
+    Ok(SessionRecord {
+        session_id: row.get(0)?,
+        player_name: row.get(1)?,
+        game_mode: row.get(2)?,
+        start_time: row.get(3)?,
+        end_time: row.get(4)?,
+        final_score: row.get(5)?,
+        highest_tile: row.get(6)?,
+        total_moves: row.get(7)?,
+        bad_moves: row.get(8)?,
+        average_time_per_move_ms: row.get(9)?,
+        game_time: row.get(10)?,
+        coaching: row.get(11)?,
+        hints_requested: row.get(12)?,
+        starting_board: row.get(13)?,
+        seed: row.get(14)?,
+        complete: row.get(15)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::Move;
+    use crate::player_data::PlayerDataRecorder;
+
+    #[test]
+    fn it_imports_and_reads_back_recordings() {
+        let mut database = Database::open_in_memory().unwrap();
+        let files = ["deploy/data/game_moves_20250801_162156_oej58.csv", "player_moves_20250731_233122.csv"];
+        let summary = database.import(&files, &DataDir::new("deploy/data")).unwrap();
+        assert_eq!((summary.files, summary.sessions), (2, 3));
+
+        let moves = schema::read_moves(files[0]).unwrap();
+        assert_eq!(summary.moves, moves.len() + 25);
+        assert_eq!(database.moves("20250801_162156_oej58").unwrap(), moves);
+        assert_eq!(database.moves("20250731_233122").unwrap().len(), 25);
+
+        let sessions = database.sessions().unwrap();
+        assert_eq!(sessions, player_data::read_session_records("deploy/data/player_sessions.csv").unwrap());
+        assert_eq!(database.session("20250801_162156_oej58").unwrap().unwrap().final_score, 1396);
+        assert!(database.session("missing").unwrap().is_none());
+        let stored = database.load_session("20250801_162156_oej58").unwrap().unwrap();
+        assert_eq!(stored.moves, moves);
+
+        // Importing again replaces rows rather than adding them
+        database.import(&files[..1], &DataDir::new("deploy/data")).unwrap();
+        let players = database.player_summaries().unwrap();
+        assert_eq!(players.iter().map(|p| p.moves).sum::<u32>() as usize, moves.len() + 25);
+        let simon = players.iter().find(|p| p.player_name == "Simon").unwrap();
+        assert_eq!((simon.games, simon.moves), (1, moves.len() as u32));
+
+        // Moves an agent made for the player are kept but not counted as theirs
+        let mut autoplay = moves[0].clone();
+        autoplay.session_id = String::from("autoplay");
+        autoplay.agent = Some(String::from("expectimax"));
+        database.insert_move(&autoplay).unwrap();
+        assert_eq!(database.moves("autoplay").unwrap(), vec![autoplay]);
+        let players = database.player_summaries().unwrap();
+        let simon = players.iter().find(|p| p.player_name == "Simon").unwrap();
+        assert_eq!((simon.games, simon.moves), (1, moves.len() as u32));
+    }
+
+    #[test]
+    fn it_imports_sessions_kept_only_in_json() {
+        crate::engine::new();
+        let root = std::env::temp_dir().join(format!("ai_2048_import_json_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        let data_dir = DataDir::new(&root);
+        let mut recorder = PlayerDataRecorder::new()
+            .with_player_name("Ann")
+            .with_data_dir(data_dir.clone())
+            .without_move_quality();
+        recorder.begin().unwrap();
+        recorder.record_move(0x1100_0000_0000_0000, Move::Left, 40, [None, Some(4.), Some(4.), Some(4.)], 0, 1).unwrap();
+        recorder.save_session_data(4, 4).unwrap();
+        // Only the monthly file is left to show the game was played
+        std::fs::remove_file(data_dir.cli_summary()).unwrap();
+
+        let mut database = Database::open_in_memory().unwrap();
+        let web_session = "deploy/data/game_session_20250801_162156_oej58.json";
+        let summary = database.import(&[web_session], &data_dir).unwrap();
+        std::fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!((summary.files, summary.moves, summary.sessions), (1, 146, 2));
+        let web = player_data::read_session_records("deploy/data/player_sessions.csv").unwrap();
+        assert_eq!(database.session("20250801_162156_oej58").unwrap().as_ref(), Some(&web[0]));
+        let cli = database.session(recorder.get_session_id()).unwrap().unwrap();
+        assert_eq!((cli.player_name.as_deref(), cli.total_moves), (Some("Ann"), 1));
+    }
+}
@@ -0,0 +1,191 @@
+use crate::agent::best_move;
+use crate::engine as GameEngine;
+use crate::engine::Board;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum Difficulty {
+    /// Only one move is possible
+    Forced,
+    /// Several moves are about as good as the best
+    Easy,
+    Medium,
+    /// One move stands out from the rest, or the game may end next turn
+    Hard,
+}
+
+impl fmt::Display for Difficulty {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let label = match self {
+            Difficulty::Forced => "FORCED",
+            Difficulty::Easy => "EASY",
+            Difficulty::Medium => "MEDIUM",
+            Difficulty::Hard => "HARD",
+        };
+        write!(f, "{}", label)
+    }
+}
+
+/// Where the difficulty levels start.
+///
+/// Gaps are relative to the best move's score so they work for any agent's scale. The defaults
+/// come from depth-2 expectimax on the recorded web games, where the best two moves are a median
+/// 0.16% apart: they rate about half the positions easy and one in six hard, with a lone best move
+/// 1% ahead counting as hard like an only move in `annotation`.
+#[derive(Debug, Clone, Copy)]
+pub struct DifficultyThresholds {
+    /// Moves within this relative gap of the best count as near-optimal
+    pub near_optimal_gap: f64,
+    /// A lone near-optimal move this far ahead of the rest makes the position hard
+    pub hard_gap: f64,
+    /// Chance of the game ending after the best move that makes the position hard
+    pub hard_death_risk: f64,
+}
+
+impl Default for DifficultyThresholds {
+    fn default() -> Self {
+        DifficultyThresholds {
+            near_optimal_gap: 0.002,
+            hard_gap: 0.01,
+            hard_death_risk: 0.2,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct DifficultyRating {
+    pub level: Difficulty,
+    /// Gap between the best and second best move, relative to the best
+    pub score_gap: f64,
+    pub near_optimal_moves: usize,
+    pub valid_moves: usize,
+    /// Probability that the best move leaves a spawn with no moves at all
+    pub death_risk: f64,
+}
+
+/// Rates how hard it is to find a good move from the agent's scores for `board`.
+pub fn rate_position(board: Board, scores: &[Option<f64>; 4]) -> DifficultyRating {
+    rate_position_with(board, scores, &DifficultyThresholds::default())
+}
+
+pub fn rate_position_with(
+    board: Board,
+    scores: &[Option<f64>; 4],
+    thresholds: &DifficultyThresholds,
+) -> DifficultyRating {
+    let mut valid: Vec<f64> = scores.iter().flatten().copied().collect();
+    valid.sort_by(|a, b| b.total_cmp(a));
+
+    let (best, scale) = match valid.first() {
+        Some(&best) => (best, best.abs().max(f64::EPSILON)),
+        None => (0., 1.),
+    };
+    let score_gap = match valid.get(1) {
+        Some(&second) => (best - second) / scale,
+        None => 0.,
+    };
+    let near_optimal_moves = valid
+        .iter()
+        .filter(|&&score| (best - score) / scale <= thresholds.near_optimal_gap)
+        .count();
+    let death_risk = match best_move(scores) {
+        Some(direction) => death_risk(GameEngine::shift(board, direction)),
+        None => 1.,
+    };
+
+    let level = if valid.len() <= 1 {
+        Difficulty::Forced
+    } else if death_risk >= thresholds.hard_death_risk
+        || (near_optimal_moves == 1 && score_gap >= thresholds.hard_gap)
+    {
+        Difficulty::Hard
+    } else if near_optimal_moves >= 2 {
+        Difficulty::Easy
+    } else {
+        Difficulty::Medium
+    };
+
+    DifficultyRating {
+        level,
+        score_gap,
+        near_optimal_moves,
+        valid_moves: valid.len(),
+        death_risk,
+    }
+}
+
+// Probability that the spawn after a move leaves a board with no moves
+fn death_risk(afterstate: Board) -> f64 {
+    let mut risk = 0.;
+    let mut empty_cells = 0;
+    for idx in 0..16 {
+        let shift = 4 * idx;
+        if (afterstate >> shift) & 0xf == 0 {
+            empty_cells += 1;
+            if GameEngine::is_game_over(afterstate | (1 << shift)) {
+                risk += 0.9;
+            }
+            if GameEngine::is_game_over(afterstate | (2 << shift)) {
+                risk += 0.1;
+            }
+        }
+    }
+    if empty_cells == 0 {
+        0.
+    } else {
+        risk / empty_cells as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_rates_by_score_gap() {
+        GameEngine::new();
+        let board = 0x1100000000000000;
+        let forced = rate_position(board, &[Some(10.), None, None, None]);
+        assert_eq!(forced.level, Difficulty::Forced);
+
+        let easy = rate_position(board, &[Some(1_000_000.), Some(1_000_000.), Some(900_000.), None]);
+        assert_eq!(easy.level, Difficulty::Easy);
+        assert_eq!(easy.near_optimal_moves, 2);
+
+        let hard = rate_position(board, &[Some(1_000_000.), Some(900_000.), Some(900_000.), None]);
+        assert_eq!(hard.level, Difficulty::Hard);
+        assert!((hard.score_gap - 0.1).abs() < 1e-9);
+    }
+
+    #[test]
+    fn it_measures_death_risk() {
+        GameEngine::new();
+        assert_eq!(death_risk(0x1100000000000000), 0.);
+        // After Left the only empty cell is bottom right, and a 2 there ends the game
+        let risky = 0x1234432112340432;
+        let rating = rate_position(risky, &[None, Some(4.), Some(5.), None]);
+        assert!((rating.death_risk - 0.9).abs() < 1e-9);
+        assert_eq!(rating.level, Difficulty::Hard);
+    }
+
+    #[test]
+    fn it_spreads_recorded_positions_over_the_levels() {
+        GameEngine::new();
+        let moves = crate::schema::read_moves("deploy/data/game_moves_20250802_143629_w22zx.csv").unwrap();
+        let mut expectimax = crate::expectimax::Expectimax::new().with_max_depth(2);
+        let mut counts = std::collections::BTreeMap::new();
+        for mv in moves.iter().step_by(3) {
+            let board = mv.board().unwrap();
+            let level = rate_position(board, &expectimax.score_moves(board)).level;
+            *counts.entry(level).or_insert(0) += 1;
+        }
+        let rated: usize = counts.values().sum();
+        for level in [Difficulty::Easy, Difficulty::Medium, Difficulty::Hard] {
+            let count = counts.get(&level).copied().unwrap_or(0);
+            assert!(count > 0 && count < rated * 2 / 3, "{:?}", counts);
+        }
+    }
+}
+
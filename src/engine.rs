@@ -1,7 +1,8 @@
 use bitintr::Popcnt;
 use rand::Rng;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Move {
     Up,
     Down,
@@ -9,6 +10,12 @@ pub enum Move {
     Right,
 }
 
+pub const MOVES: [Move; 4] = [Move::Up, Move::Down, Move::Left, Move::Right];
+
+/// Exponent of the highest tile a board read from outside may hold, a 16384. The lookup tables
+/// stop short of a line of four 32768 tiles, so those are turned away.
+pub const MAX_TILE_EXPONENT: u8 = 14;
+
 struct Stores {
     shift_left: [Board; 0xffff],
     shift_right: [Board; 0xffff],
@@ -31,10 +38,7 @@ pub fn new() {
 pub fn get_score(board: Board) -> Score {
     (0..4).fold(0, |acc, idx| {
         let row_val = extract_line(board, idx);
-        let row_score;
-        unsafe {
-            row_score = STORES.score.get_unchecked(row_val as usize);
-        }
+        let row_score = unsafe { stores().score.get_unchecked(row_val as usize) };
         acc + row_score
     })
 }
@@ -63,7 +67,7 @@ pub fn transpose(x: Board) -> Board {
     let b1 = a & 0xFF00FF0000FF00FF;
     let b2 = a & 0x00FF00FF00000000;
     let b3 = a & 0x00000000FF00FF00;
-    return b1 | (b2 >> 24) | (b3 << 24);
+    b1 | (b2 >> 24) | (b3 << 24)
 }
 
 pub fn extract_line(board: Board, line_idx: u64) -> Line {
@@ -75,15 +79,14 @@ pub fn get_tile_val(board: Board, idx: usize) -> u16 {
 }
 
 pub fn line_to_vec(line: Line) -> Vec<Tile> {
-    let tiles = (0..4).fold(Vec::new(), |mut tiles, tile_idx| {
+    (0..4).fold(Vec::new(), |mut tiles, tile_idx| {
         tiles.push(line >> ((3 - tile_idx) * 4) & 0xf);
         tiles
-    });
-    tiles
+    })
 }
 
 pub fn is_game_over(board: Board) -> bool {
-    for direction in vec![Move::Up, Move::Down, Move::Left, Move::Right] {
+    for &direction in &MOVES {
         let new_board = shift(board, direction);
         if new_board != board {
             return false;
@@ -98,7 +101,7 @@ pub fn count_empty(board: Board) -> u64 {
 }
 
 pub fn to_str(board: Board) -> String {
-    let board: Vec<_> = to_vec(board).iter().map(|x| format_val(x)).collect();
+    let board: Vec<_> = to_vec(board).iter().map(format_val).collect();
     format!(
         "\r
         {}|{}|{}|{}\r
@@ -136,6 +139,11 @@ static mut STORES: Stores = Stores {
     score: [0; 0xffff],
 };
 
+// The stores are only written by `new`, before any lookups happen
+fn stores() -> &'static Stores {
+    unsafe { &*std::ptr::addr_of!(STORES) }
+}
+
 unsafe fn create_stores() {
     let mut val = 0;
     while val < 0xffff {
@@ -151,12 +159,24 @@ unsafe fn create_stores() {
     }
 }
 
-// Credit to Nneonneo
 pub fn insert_random_tile(board: Board) -> Board {
-    let mut rng = rand::thread_rng();
+    insert_random_tile_with(board, &mut rand::thread_rng())
+}
+
+/// Makes a move, drawing the spawned tile from `rng` so games can be replayed from a seed.
+pub fn make_move_with<R: Rng + ?Sized>(board: Board, direction: Move, rng: &mut R) -> Board {
+    let new_board = shift(board, direction);
+    if board != new_board {
+        return insert_random_tile_with(new_board, rng);
+    }
+    board
+}
+
+// Credit to Nneonneo
+pub fn insert_random_tile_with<R: Rng + ?Sized>(board: Board, rng: &mut R) -> Board {
     let mut index = rng.gen_range(0, count_empty(board));
     let mut tmp = board;
-    let mut tile = generate_random_tile();
+    let mut tile = generate_random_tile(rng);
     loop {
         while (tmp & 0xf) != 0 {
             tmp >>= 4;
@@ -169,11 +189,10 @@ pub fn insert_random_tile(board: Board) -> Board {
         tmp >>= 4;
         tile <<= 4;
     }
-    return board | tile;
+    board | tile
 }
 
-fn generate_random_tile() -> Tile {
-    let mut rng = rand::thread_rng();
+fn generate_random_tile<R: Rng + ?Sized>(rng: &mut R) -> Tile {
     if rng.gen_range(0, 10) < 9 {
         1
     } else {
@@ -185,8 +204,8 @@ fn shift_rows(board: Board, move_dir: Move) -> Board {
     (0..4).fold(0, |new_board, row_idx| {
         let row_val = extract_line(board, row_idx);
         let new_row_val = match move_dir {
-            Move::Left => unsafe { STORES.shift_left.get_unchecked(row_val as usize) },
-            Move::Right => unsafe { STORES.shift_right.get_unchecked(row_val as usize) },
+            Move::Left => unsafe { stores().shift_left.get_unchecked(row_val as usize) },
+            Move::Right => unsafe { stores().shift_right.get_unchecked(row_val as usize) },
             _ => panic!("Trying to move up or down in shift rows"),
         };
         new_board | (new_row_val << (48 - (16 * row_idx)))
@@ -198,8 +217,8 @@ fn shift_cols(board: Board, move_dir: Move) -> Board {
     (0..4).fold(0, |new_board, col_idx| {
         let col_val = extract_line(transpose_board, col_idx);
         let new_col_val = match move_dir {
-            Move::Up => unsafe { STORES.shift_up.get_unchecked(col_val as usize) },
-            Move::Down => unsafe { STORES.shift_down.get_unchecked(col_val as usize) },
+            Move::Up => unsafe { stores().shift_up.get_unchecked(col_val as usize) },
+            Move::Down => unsafe { stores().shift_down.get_unchecked(col_val as usize) },
             _ => panic!("Trying to move left or right in shift cols"),
         };
         new_board | (new_col_val << (12 - (4 * col_idx)))
@@ -231,7 +250,7 @@ fn shift_vec(vec: Vec<Tile>, direction: Move) -> Vec<Tile> {
 
 fn shift_vec_right(vec: Vec<Tile>) -> Vec<Tile> {
     let rev_vec: Vec<Tile> = vec.into_iter().rev().collect();
-    shift_vec_left(rev_vec).iter().rev().map(|&x| x).collect()
+    shift_vec_left(rev_vec).iter().rev().copied().collect()
 }
 
 fn shift_vec_left(mut vec: Vec<Tile>) -> Vec<Tile> {
@@ -243,16 +262,16 @@ fn shift_vec_left(mut vec: Vec<Tile>) -> Vec<Tile> {
 
 fn calculate_left_shift(slice: &mut [Tile]) {
     let mut acc = 0;
-    for idx in 0..slice.len() {
-        let val = slice[idx];
+    for tile in slice.iter_mut() {
+        let val = *tile;
         if acc != 0 && acc == val {
-            slice[idx] = 0;
+            *tile = 0;
             acc += 1;
             break;
         } else if acc != 0 && val != 0 && acc != val {
             break;
         } else if acc == 0 && val != 0 {
-            slice[idx] = 0;
+            *tile = 0;
             acc = val;
         };
     }
@@ -263,8 +282,7 @@ fn calculate_left_shift(slice: &mut [Tile]) {
 fn calc_score(line: Line) -> Score {
     let mut score = 0;
     let tiles = line_to_vec(line);
-    for i in 0..4 {
-        let tile_val = tiles[i];
+    for tile_val in tiles {
         if tile_val >= 2 {
             // the score is the total sum of the tile and all intermediate merged tiles
             score += (tile_val - 1) * (1 << tile_val);
@@ -295,7 +313,7 @@ fn extract_tile(board: Board, idx: usize) -> Tile {
 
 fn format_val(val: &u8) -> String {
     match val {
-        0 => return String::from("       "),
+        0 => String::from("       "),
         &x => {
             let mut x = (2_i32.pow(x as u32)).to_string();
             while x.len() < 7 {
@@ -309,6 +327,55 @@ fn format_val(val: &u8) -> String {
     }
 }
 
+/// Builds a board from 16 tile exponents in the order written by `to_vec`.
+pub fn from_vec(tiles: &[u8]) -> Board {
+    tiles
+        .iter()
+        .take(16)
+        .fold(0, |board, &tile| board << 4 | (tile as Board & 0xf))
+}
+
+/// Reads a board typed by a person: either the packed hex form, such as `0x1100000000000000`,
+/// or 16 tile values row by row separated by commas or spaces, with 0 for an empty cell. A board
+/// must have a move to make, so `new` has to have been called.
+pub fn parse_position(text: &str) -> Result<Board, String> {
+    let text = text.trim();
+    let hex = text.strip_prefix("0x").or_else(|| text.strip_prefix("0X"));
+    let board = match hex {
+        Some(hex) => Board::from_str_radix(hex, 16).map_err(|_| format!("'{}' is not a hex board", text))?,
+        None if text.len() == 16 && text.chars().all(|c| c.is_ascii_hexdigit()) => {
+            Board::from_str_radix(text, 16).unwrap()
+        }
+        None => {
+            let tiles = text
+                .split(|c: char| c == ',' || c.is_whitespace())
+                .filter(|value| !value.is_empty())
+                .map(|value| match value.parse::<u32>() {
+                    Ok(0) => Ok(0),
+                    Ok(tile) if tile >= 2 && tile.is_power_of_two() && tile.trailing_zeros() < 16 => {
+                        Ok(tile.trailing_zeros() as u8)
+                    }
+                    _ => Err(format!("'{}' is not a tile value", value)),
+                })
+                .collect::<Result<Vec<u8>, String>>()?;
+            if tiles.len() != 16 {
+                return Err(format!("Expected 16 tiles but found {}", tiles.len()));
+            }
+            from_vec(&tiles)
+        }
+    };
+    if board == 0 {
+        return Err(String::from("The board has no tiles"));
+    }
+    if to_vec(board).iter().any(|&tile| tile > MAX_TILE_EXPONENT) {
+        return Err(format!("Tiles above {} are not supported", 1 << MAX_TILE_EXPONENT));
+    }
+    if is_game_over(board) {
+        return Err(String::from("The board has no moves left"));
+    }
+    Ok(board)
+}
+
 pub fn get_highest_tile_val(board: Board) -> Tile {
     let max_tile = (0..16)
         .map(|idx| get_tile(board, idx))
@@ -351,6 +418,25 @@ mod tests {
         assert_eq!(count_empty(game), 0);
     }
 
+    #[test]
+    fn it_parses_positions() {
+        new();
+        assert_eq!(parse_position("0x1100000000000002"), Ok(0x1100_0000_0000_0002));
+        assert_eq!(parse_position("1100000000000002"), Ok(0x1100_0000_0000_0002));
+        assert_eq!(
+            parse_position("2,2,0,0, 0,0,0,0, 0,0,0,0, 0,0,0,4"),
+            Ok(0x1100_0000_0000_0002)
+        );
+        assert_eq!(parse_position("2 2 0 0\n0 0 0 0\n0 0 0 0\n0 0 0 4"), Ok(0x1100_0000_0000_0002));
+        assert!(parse_position("2,3,0,0,0,0,0,0,0,0,0,0,0,0,0,0").is_err());
+        assert!(parse_position("2,2,0").is_err());
+        assert!(parse_position("0x0").is_err());
+        assert!(parse_position("0xffff000000000000").is_err());
+        assert!(parse_position("32768,2,0,0,0,0,0,0,0,0,0,0,0,0,0,0").is_err());
+        assert!(parse_position("16384,2,0,0,0,0,0,0,0,0,0,0,0,0,0,0").is_ok());
+        assert_eq!(parse_position("0x1212212112122121"), Err(String::from("The board has no moves left")));
+    }
+
     #[test]
     fn test_shift_left() {
         new();
@@ -440,4 +526,11 @@ mod tests {
         assert_eq!(get_tile_val(game, 10), 1024);
         assert_eq!(get_tile_val(game, 15), 32768);
     }
+
+    #[test]
+    fn it_round_trips_through_vec() {
+        let game = 0x123456789abcdef0;
+        assert_eq!(to_vec(game)[0], 1);
+        assert_eq!(from_vec(&to_vec(game)), game);
+    }
 }
@@ -1,13 +1,98 @@
 use crate::engine as GameEngine;
-use crate::engine::{Board, Move};
+use crate::engine::{Board, Move, MOVES};
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::error::Error;
+use std::path::Path;
+use std::sync::Arc;
 use std::thread;
 
-static mut HEURISTIC_SCORES: [f64; 0xffff] = [0.; 0xffff];
+/// Weights of the line heuristic evaluated at the leaves of the search.
+///
+/// The defaults are Nneonneo's values; `tune` searches for better ones.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct HeuristicWeights {
+    pub lost_penalty: f64,
+    pub sum_power: f64,
+    pub sum_weight: f64,
+    pub empty_weight: f64,
+    pub merges_weight: f64,
+    pub monotonicity_power: f64,
+    pub monotonicity_weight: f64,
+}
+
+impl HeuristicWeights {
+    pub const LEN: usize = 7;
+
+    pub fn to_array(&self) -> [f64; Self::LEN] {
+        [
+            self.lost_penalty,
+            self.sum_power,
+            self.sum_weight,
+            self.empty_weight,
+            self.merges_weight,
+            self.monotonicity_power,
+            self.monotonicity_weight,
+        ]
+    }
+
+    /// Reads weights written by `tune`.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn Error>> {
+        let path = path.as_ref();
+        let json = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        Ok(serde_json::from_str(&json).map_err(|e| format!("{}: {}", path.display(), e))?)
+    }
+
+    pub fn from_array(values: [f64; Self::LEN]) -> Self {
+        HeuristicWeights {
+            lost_penalty: values[0],
+            sum_power: values[1],
+            sum_weight: values[2],
+            empty_weight: values[3],
+            merges_weight: values[4],
+            monotonicity_power: values[5],
+            monotonicity_weight: values[6],
+        }
+    }
+}
+
+impl Default for HeuristicWeights {
+    fn default() -> Self {
+        HeuristicWeights {
+            lost_penalty: 200000.,
+            sum_power: 3.5,
+            sum_weight: 11.,
+            empty_weight: 270.,
+            merges_weight: 700.,
+            monotonicity_power: 4.,
+            monotonicity_weight: 47.,
+        }
+    }
+}
+
+/// Scores the boards at the leaves of the search.
+///
+/// Leaves are always boards just after a move, before the new tile is spawned.
+pub trait Evaluator: Send + Sync {
+    fn evaluate(&self, board: Board) -> f64;
+}
+
+// Heuristic score of every possible line, indexed by the 16 bit line value.
+struct HeuristicTable(Vec<f64>);
+
+impl Evaluator for HeuristicTable {
+    fn evaluate(&self, board: Board) -> f64 {
+        get_heurisitic_score(board, self)
+    }
+}
 
-unsafe fn create_heuristic_score_table() {
-    for i in 0..0xffff {
-        HEURISTIC_SCORES[i] = calc_heuristic_score(i as u64);
+impl HeuristicTable {
+    fn new(weights: &HeuristicWeights) -> Self {
+        HeuristicTable(
+            (0..=0xffff)
+                .map(|line| calc_heuristic_score(line, weights))
+                .collect(),
+        )
     }
 }
 
@@ -39,23 +124,97 @@ struct TranspositionEntry {
     move_depth: u64,
 }
 
-pub struct Expectimax(pub u64, pub u64);
+/// Single-threaded search.
+pub struct Expectimax {
+    /// States searched over every search so far
+    pub total_states: u64,
+    /// States searched by the largest single search
+    pub max_states: u64,
+    evaluator: Arc<dyn Evaluator>,
+    max_depth: Option<u64>,
+}
 
 impl Expectimax {
     pub fn new() -> Self {
-        unsafe { create_heuristic_score_table() };
-        Expectimax(0, 0)
+        Self::with_weights(HeuristicWeights::default())
+    }
+
+    pub fn with_weights(weights: HeuristicWeights) -> Self {
+        Self::with_evaluator(Arc::new(HeuristicTable::new(&weights)))
+    }
+
+    pub fn with_evaluator(evaluator: Arc<dyn Evaluator>) -> Self {
+        Expectimax {
+            total_states: 0,
+            max_states: 0,
+            evaluator,
+            max_depth: None,
+        }
+    }
+
+    /// Caps the adaptive search depth, trading strength for speed.
+    pub fn with_max_depth(mut self, max_depth: u64) -> Self {
+        self.max_depth = Some(max_depth);
+        self
     }
 
     pub fn get_next_move(&mut self, board: Board) -> Option<Move> {
-        let depth = 3.max(count_unique(board) - 2) as u64;
-        //let depth = depth.min(6);
+        let depth = search_depth(board, self.max_depth);
         let mut state_count = 0;
-        let result = expectimax(board, Node::Max, depth, 1., &mut HashMap::new(), &mut state_count).move_dir;
-        self.0 += state_count;
-        self.1 = self.1.max(state_count);
+        let result = expectimax(
+            board,
+            Node::Max,
+            depth,
+            1.,
+            &mut HashMap::new(),
+            &mut state_count,
+            self.evaluator.as_ref(),
+        )
+        .move_dir;
+        self.total_states += state_count;
+        self.max_states = self.max_states.max(state_count);
         result
     }
+
+    /// Search value of each move in `[Up, Down, Left, Right]` order, `None` if the move is invalid.
+    pub fn score_moves(&mut self, board: Board) -> [Option<f64>; 4] {
+        let depth = search_depth(board, self.max_depth);
+        let mut map = HashMap::new();
+        let mut state_count = 0;
+        let mut scores = [None; 4];
+        for (score, &direction) in scores.iter_mut().zip(MOVES.iter()) {
+            let new_board = GameEngine::shift(board, direction);
+            if new_board != board {
+                let result = expectimax(
+                    new_board,
+                    Node::Chance,
+                    depth,
+                    1.,
+                    &mut map,
+                    &mut state_count,
+                    self.evaluator.as_ref(),
+                );
+                *score = Some(result.score);
+            }
+        }
+        self.total_states += state_count;
+        self.max_states = self.max_states.max(state_count);
+        scores
+    }
+}
+
+impl Default for Expectimax {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn search_depth(board: Board, max_depth: Option<u64>) -> u64 {
+    let depth = 3.max(count_unique(board) - 2) as u64;
+    match max_depth {
+        Some(max_depth) => depth.min(max_depth),
+        None => depth,
+    }
 }
 
 fn expectimax(
@@ -65,11 +224,12 @@ fn expectimax(
     cum_prob: f32,
     map: &mut TranspositionTable,
     state_count: &mut u64,
+    evaluator: &dyn Evaluator,
 ) -> ExpectimaxResult {
     *state_count += 1;
     match node {
-        Node::Max => return evaluate_max(board, move_depth, cum_prob, map, state_count),
-        Node::Chance => return evaluate_chance(board, move_depth, cum_prob, map, state_count),
+        Node::Max => evaluate_max(board, move_depth, cum_prob, map, state_count, evaluator),
+        Node::Chance => evaluate_chance(board, move_depth, cum_prob, map, state_count, evaluator),
     }
 }
 
@@ -79,25 +239,24 @@ fn evaluate_max(
     cum_prob: f32,
     map: &mut TranspositionTable,
     state_count: &mut u64,
+    evaluator: &dyn Evaluator,
 ) -> ExpectimaxResult {
-    let mut best_score = 0.;
+    let mut best_score = f64::NEG_INFINITY;
     let mut best_move = None;
-    for &direction in &[Move::Up, Move::Down, Move::Left, Move::Right] {
-        let new_board;
-        match direction {
-            Move::Up => new_board = GameEngine::shift(board, Move::Up),
-            Move::Down => new_board = GameEngine::shift(board, Move::Down),
-            Move::Left => new_board = GameEngine::shift(board, Move::Left),
-            Move::Right => new_board = GameEngine::shift(board, Move::Right),
-        }
+    for &direction in &MOVES {
+        let new_board = GameEngine::shift(board, direction);
         if new_board != board {
-            let score = expectimax(new_board, Node::Chance, move_depth, cum_prob, map, state_count).score;
+            let score = expectimax(new_board, Node::Chance, move_depth, cum_prob, map, state_count, evaluator).score;
             if score > best_score {
                 best_score = score;
                 best_move = Some(direction);
             }
         }
     }
+    // A board with no moves is lost and worth nothing
+    if best_move.is_none() {
+        best_score = 0.;
+    }
     ExpectimaxResult {
         score: best_score,
         move_dir: best_move,
@@ -110,10 +269,11 @@ fn evaluate_chance(
     cum_prob: f32,
     map: &mut TranspositionTable,
     state_count: &mut u64,
+    evaluator: &dyn Evaluator,
 ) -> ExpectimaxResult {
     if move_depth == 0 || cum_prob < 0.0001 {
         return ExpectimaxResult {
-            score: get_heurisitic_score(board) as f64,
+            score: evaluator.evaluate(board),
             move_dir: None,
         };
     }
@@ -140,12 +300,14 @@ fn evaluate_chance(
     while tiles_searched < num_empty_tiles {
         if (tmp & 0xf) == 0 {
             let new_board = board | insert_tile;
-            score +=
-                expectimax(new_board, Node::Max, move_depth - 1, cum_prob * 0.9, map, state_count).score * 0.9;
+            score += expectimax(new_board, Node::Max, move_depth - 1, cum_prob * 0.9, map, state_count, evaluator)
+                .score
+                * 0.9;
 
             let new_board = board | (insert_tile << 1);
-            score +=
-                expectimax(new_board, Node::Max, move_depth - 1, cum_prob * 0.1, map, state_count).score * 0.1;
+            score += expectimax(new_board, Node::Max, move_depth - 1, cum_prob * 0.1, map, state_count, evaluator)
+                .score
+                * 0.1;
 
             tiles_searched += 1;
         }
@@ -153,7 +315,7 @@ fn evaluate_chance(
         insert_tile <<= 4;
     }
 
-    score = score / num_empty_tiles as f64;
+    score /= num_empty_tiles as f64;
 
     map.insert(board, TranspositionEntry { score, move_depth });
 
@@ -163,17 +325,59 @@ fn evaluate_chance(
     }
 }
 
-pub struct ExpectimaxMultithread;
+pub struct ExpectimaxMultithread {
+    evaluator: Arc<dyn Evaluator>,
+    max_depth: Option<u64>,
+}
 
 impl ExpectimaxMultithread {
     pub fn new() -> Self {
-        unsafe { create_heuristic_score_table() };
-        ExpectimaxMultithread
+        Self::with_weights(HeuristicWeights::default())
+    }
+
+    pub fn with_weights(weights: HeuristicWeights) -> Self {
+        Self::with_evaluator(Arc::new(HeuristicTable::new(&weights)))
+    }
+
+    pub fn with_evaluator(evaluator: Arc<dyn Evaluator>) -> Self {
+        ExpectimaxMultithread {
+            evaluator,
+            max_depth: None,
+        }
+    }
+
+    /// Caps the adaptive search depth, trading strength for speed.
+    pub fn with_max_depth(mut self, max_depth: u64) -> Self {
+        self.max_depth = Some(max_depth);
+        self
     }
 
     pub fn get_next_move(&mut self, board: Board) -> Option<Move> {
-        let depth = 3.max(count_unique(board) - 2) as u64;
-        evaluate_multithread(board, depth, 1.).move_dir
+        let depth = search_depth(board, self.max_depth);
+        evaluate_multithread(board, depth, 1., &self.evaluator).move_dir
+    }
+
+    /// Search value of each move in `[Up, Down, Left, Right]` order, `None` if the move is invalid.
+    pub fn score_moves(&mut self, board: Board) -> [Option<f64>; 4] {
+        let depth = search_depth(board, self.max_depth);
+        let threads: Vec<_> = MOVES
+            .iter()
+            .map(|&direction| spawn_move_computation(board, depth, 1., direction, Arc::clone(&self.evaluator)))
+            .collect();
+        let mut scores = [None; 4];
+        for (score, thread) in scores.iter_mut().zip(threads) {
+            let result = thread.join().unwrap();
+            if result.move_dir.is_some() {
+                *score = Some(result.score);
+            }
+        }
+        scores
+    }
+}
+
+impl Default for ExpectimaxMultithread {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
@@ -181,23 +385,27 @@ fn evaluate_multithread(
     board: Board,
     move_depth: u64,
     cum_prob: f32,
+    evaluator: &Arc<dyn Evaluator>,
 ) -> ExpectimaxResult {
     let mut threads = vec![];
-    for &direction in &[Move::Up, Move::Down, Move::Left, Move::Right] {
+    for &direction in &MOVES {
         // spawn computation threads using function and push to vec
-        let board_copy = board;
         threads.push(spawn_move_computation(
-            board_copy, move_depth, cum_prob, direction,
+            board,
+            move_depth,
+            cum_prob,
+            direction,
+            Arc::clone(evaluator),
         ));
     }
 
     let mut best_result = ExpectimaxResult {
-        score: 0.,
+        score: f64::NEG_INFINITY,
         move_dir: None,
     };
     for thread in threads {
         let result = thread.join().unwrap();
-        if result.score > best_result.score {
+        if result.move_dir.is_some() && result.score > best_result.score {
             best_result = result;
         }
     }
@@ -209,15 +417,10 @@ fn spawn_move_computation(
     move_depth: u64,
     cum_prob: f32,
     direction: Move,
+    evaluator: Arc<dyn Evaluator>,
 ) -> thread::JoinHandle<ExpectimaxResult> {
     thread::spawn(move || {
-        let new_board;
-        match direction {
-            Move::Up => new_board = GameEngine::shift(board, Move::Up),
-            Move::Down => new_board = GameEngine::shift(board, Move::Down),
-            Move::Left => new_board = GameEngine::shift(board, Move::Left),
-            Move::Right => new_board = GameEngine::shift(board, Move::Right),
-        }
+        let new_board = GameEngine::shift(board, direction);
         if new_board != board {
             return ExpectimaxResult {
                 score: expectimax(
@@ -226,7 +429,8 @@ fn spawn_move_computation(
                     move_depth,
                     cum_prob,
                     &mut HashMap::new(),
-                    &mut 0
+                    &mut 0,
+                    evaluator.as_ref(),
                 )
                 .score,
                 move_dir: Some(direction),
@@ -257,50 +461,45 @@ fn count_unique(board: Board) -> i32 {
         bitset &= bitset - 1;
         count += 1;
     }
-    return count;
+    count
 }
 
-fn get_heurisitic_score(board: Board) -> f64 {
+fn get_heurisitic_score(board: Board, table: &HeuristicTable) -> f64 {
     let transpose_board = GameEngine::transpose(board);
     (0..4).fold(0., |score, line_idx| {
         let row_val = GameEngine::extract_line(board, line_idx);
         let col_val = GameEngine::extract_line(transpose_board, line_idx);
-        let row_score = unsafe { HEURISTIC_SCORES.get_unchecked(row_val as usize) };
-        let col_score = unsafe { HEURISTIC_SCORES.get_unchecked(col_val as usize) };
+        let row_score = unsafe { table.0.get_unchecked(row_val as usize) };
+        let col_score = unsafe { table.0.get_unchecked(col_val as usize) };
         score + row_score + col_score
     })
 }
 
 // The heuristics developed by Nneonneo were used: https://github.com/nneonneo/2048-ai/blob/master/2048.cpp
-fn calc_heuristic_score(line: u64) -> f64 {
-    const LOST_PENALTY: f64 = 200000.;
+fn calc_heuristic_score(line: u64, weights: &HeuristicWeights) -> f64 {
     let tiles = GameEngine::line_to_vec(line);
-    LOST_PENALTY + calc_empty(&tiles) + calc_merges(&tiles)
-        - calc_monotonicity(&tiles)
-        - calc_sum(&tiles)
+    weights.lost_penalty + calc_empty(&tiles, weights) + calc_merges(&tiles, weights)
+        - calc_monotonicity(&tiles, weights)
+        - calc_sum(&tiles, weights)
 }
 
-fn calc_sum(line: &Vec<u64>) -> f64 {
-    const SUM_POWER: f64 = 3.5;
-    const SUM_WEIGHT: f64 = 11.;
+fn calc_sum(line: &[u64], weights: &HeuristicWeights) -> f64 {
     line.iter()
-        .fold(0., |acc, &tile_val| acc + (tile_val as f64).powf(SUM_POWER))
-        * SUM_WEIGHT
+        .fold(0., |acc, &tile_val| acc + (tile_val as f64).powf(weights.sum_power))
+        * weights.sum_weight
 }
 
-fn calc_empty(line: &Vec<u64>) -> f64 {
-    const EMPTY_WEIGHT: f64 = 270.0;
+fn calc_empty(line: &[u64], weights: &HeuristicWeights) -> f64 {
     line.iter().fold(0., |num_empty_tiles, &tile_val| {
         if tile_val == 0 {
             num_empty_tiles + 1.
         } else {
             num_empty_tiles
         }
-    }) * EMPTY_WEIGHT
+    }) * weights.empty_weight
 }
 
-fn calc_merges(line: &Vec<u64>) -> f64 {
-    const MERGES_WEIGHT: f64 = 700.0;
+fn calc_merges(line: &[u64], weights: &HeuristicWeights) -> f64 {
     let mut prev = 0;
     let mut counter = 0.;
     let mut merges = 0.;
@@ -316,25 +515,23 @@ fn calc_merges(line: &Vec<u64>) -> f64 {
     if counter > 0. {
         merges += 1. + counter;
     }
-    merges * MERGES_WEIGHT
+    merges * weights.merges_weight
 }
 
-fn calc_monotonicity(line: &Vec<u64>) -> f64 {
-    const MONOTONICITY_POWER: f64 = 4.;
-    const MONOTONICITY_WEIGHT: f64 = 47.;
-
+fn calc_monotonicity(line: &[u64], weights: &HeuristicWeights) -> f64 {
+    let power = weights.monotonicity_power;
     let mut monotonicity_left = 0.;
     let mut monotonicity_right = 0.;
     for i in 1..4 {
         let tile1 = line[i - 1] as f64;
         let tile2 = line[i] as f64;
         if tile1 > tile2 {
-            monotonicity_left += tile1.powf(MONOTONICITY_POWER) - tile2.powf(MONOTONICITY_POWER);
+            monotonicity_left += tile1.powf(power) - tile2.powf(power);
         } else {
-            monotonicity_right += tile2.powf(MONOTONICITY_POWER) - tile1.powf(MONOTONICITY_POWER);
+            monotonicity_right += tile2.powf(power) - tile1.powf(power);
         }
     }
-    monotonicity_left.min(monotonicity_right) * MONOTONICITY_WEIGHT
+    monotonicity_left.min(monotonicity_right) * weights.monotonicity_weight
 }
 
 #[cfg(test)]
@@ -348,4 +545,40 @@ mod tests {
         let game = 0x0000010000000010;
         assert_eq!(count_unique(game), 1);
     }
+
+    #[test]
+    fn it_calc_heuristic_score() {
+        let weights = HeuristicWeights::default();
+        assert_eq!(calc_heuristic_score(0x0000, &weights), 200000. + 4. * 270.);
+        assert_eq!(
+            calc_heuristic_score(0x4321, &weights),
+            200000.
+                - (11. * ((4_f64).powf(3.5) + (3_f64).powf(3.5) + (2_f64).powf(3.5) + 1.))
+        );
+    }
+
+    #[test]
+    fn it_weights_array_round_trip() {
+        let weights = HeuristicWeights::default();
+        assert_eq!(HeuristicWeights::from_array(weights.to_array()), weights);
+    }
+
+    #[test]
+    fn it_scores_only_valid_moves() {
+        GameEngine::new();
+        // Only Up and Right change this board
+        let board = 0x0000000000001200;
+        let mut expectimax = Expectimax::new().with_max_depth(1);
+        let scores = expectimax.score_moves(board);
+        assert!(scores[0].is_some() && scores[3].is_some());
+        assert_eq!((scores[1], scores[2]), (None, None));
+        assert_eq!(ExpectimaxMultithread::new().with_max_depth(1).score_moves(board), scores);
+    }
+
+    #[test]
+    fn it_caps_search_depth() {
+        let game = 0x123456789abc0000;
+        assert_eq!(search_depth(game, None), 10);
+        assert_eq!(search_depth(game, Some(2)), 2);
+    }
 }
@@ -1,4 +1,21 @@
+pub mod agent;
+pub mod annotation;
+pub mod data_dir;
+#[cfg(feature = "sqlite")]
+pub mod database;
+pub mod difficulty;
 pub mod engine;
 pub mod expectimax;
+pub mod monte_carlo;
+pub mod move_quality;
+pub mod ntuple;
+pub mod simulation;
+pub mod solver;
+pub mod store;
+pub mod timer;
+pub mod validation;
 pub mod wasm;
+pub mod web_data;
 pub mod player_data;
+pub mod puzzle;
+pub mod schema;
@@ -1,13 +1,218 @@
+mod puzzle_mode;
+mod replay;
+mod tui;
+
+use ai_2048::agent::{self, Agent, NTupleAgent, AGENT_NAMES};
+use ai_2048::data_dir::{DataDir, DATA_DIR_ENV};
+#[cfg(feature = "sqlite")]
+use ai_2048::database::Database;
+use ai_2048::difficulty;
 use ai_2048::engine as GameEngine;
 use ai_2048::engine::{Board, Move};
-use ai_2048::expectimax::Expectimax;
-use ai_2048::player_data::PlayerDataRecorder;
-use std::io::{self, Write};
-use std::time::Instant;
+use ai_2048::expectimax::{Expectimax, ExpectimaxMultithread, HeuristicWeights};
+use ai_2048::move_quality::{MoveGrade, MoveQualityAnalyzer};
+use ai_2048::ntuple;
+#[cfg(feature = "sqlite")]
+use ai_2048::player_data::SessionRecord;
+use ai_2048::player_data::{self, CoachingMode, GameMode, PlayerDataRecorder};
+#[cfg(feature = "sqlite")]
+use ai_2048::store::{FileStore, Replicated};
+use ai_2048::timer::{self, GameTimer};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+use tui::TerminalUi;
+
+enum PlayerInput {
+    Move(Move),
+    Hint,
+    Quit,
+    TimeUp,
+}
+
+/// Commands while an agent plays.
+enum SpectatorInput {
+    TogglePause,
+    Step,
+    TakeOver,
+    Quit,
+    TimeUp,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Control {
+    Player,
+    Agent { paused: bool },
+}
+
+/// A position waiting for the player's move.
+#[derive(Clone)]
+struct Turn {
+    board: Board,
+    /// The line above the board, such as the move number and score
+    status: String,
+    /// The AI panel, one line each
+    analysis: Vec<String>,
+    /// What the agent is doing in autoplay, `None` when the player has control
+    autoplay: Option<String>,
+    /// Whether H shows the hidden AI panel
+    hints: bool,
+}
+
+/// How the game is shown to the player and how their moves are read.
+trait Frontend {
+    fn show(&mut self, turn: &Turn, timer: &GameTimer);
+    /// Waits for the player's move. Reaction times are measured around this call.
+    fn next_input(&mut self, timer: &GameTimer) -> PlayerInput;
+    /// Waits for a spectator command during autoplay, for at most `wait` or indefinitely with
+    /// `None`. Returns `None` once the wait is over.
+    fn spectator_input(&mut self, timer: &GameTimer, wait: Option<Duration>) -> Option<SpectatorInput>;
+    /// A message about the last move, shown with the next position.
+    fn notify(&mut self, message: &str);
+}
+
+/// The line-based client, used when stdin is not a terminal or with `--line-mode`.
+/// Every move has to be confirmed with Enter.
+struct LineUi {
+    input: Receiver<String>,
+    hints: bool,
+}
+
+impl Frontend for LineUi {
+    fn show(&mut self, turn: &Turn, timer: &GameTimer) {
+        println!();
+        println!("{}", turn.status);
+        println!("{}", GameEngine::to_str(turn.board));
+        if let Some(clock) = timer.display() {
+            println!("{}", clock);
+        }
+        for line in &turn.analysis {
+            println!("{}", line);
+        }
+        if let Some(autoplay) = &turn.autoplay {
+            println!("{}", autoplay);
+        }
+        println!();
+        self.hints = turn.hints;
+    }
+
+    fn next_input(&mut self, timer: &GameTimer) -> PlayerInput {
+        let prompt = if self.hints {
+            "Your move (WASD, H for a hint or Q to quit): "
+        } else {
+            "Your move (WASD or Q to quit): "
+        };
+        get_player_move(&self.input, timer.deadline(), prompt)
+    }
+
+    fn spectator_input(&mut self, timer: &GameTimer, wait: Option<Duration>) -> Option<SpectatorInput> {
+        let deadline = match (wait.map(|wait| Instant::now() + wait), timer.deadline()) {
+            (Some(wait), Some(deadline)) => Some(wait.min(deadline)),
+            (wait, deadline) => wait.or(deadline),
+        };
+        loop {
+            let line = match deadline {
+                Some(deadline) => self.input.recv_timeout(deadline.saturating_duration_since(Instant::now())),
+                None => self.input.recv().map_err(|_| RecvTimeoutError::Disconnected),
+            };
+            match line {
+                Ok(line) => match line.trim().to_lowercase().as_str() {
+                    "p" | "" => return Some(SpectatorInput::TogglePause),
+                    "n" => return Some(SpectatorInput::Step),
+                    "t" => return Some(SpectatorInput::TakeOver),
+                    "q" | "quit" | "exit" => return Some(SpectatorInput::Quit),
+                    _ => println!("Invalid input! Use P to pause, N to step, T to take over or Q to quit."),
+                },
+                Err(RecvTimeoutError::Timeout) if timer.is_expired() => return Some(SpectatorInput::TimeUp),
+                Err(RecvTimeoutError::Timeout) => return None,
+                // Nobody can pause any more, so the agent plays on at its own pace
+                Err(RecvTimeoutError::Disconnected) => {
+                    if let Some(deadline) = deadline {
+                        thread::sleep(deadline.saturating_duration_since(Instant::now()));
+                    }
+                    return if timer.is_expired() {
+                        Some(SpectatorInput::TimeUp)
+                    } else if wait.is_none() {
+                        Some(SpectatorInput::Step)
+                    } else {
+                        None
+                    };
+                }
+            }
+        }
+    }
+
+    fn notify(&mut self, message: &str) {
+        println!("{}", message);
+    }
+}
+
+struct Options {
+    agent: String,
+    /// Heuristic weights for the expectimax agents, from `--weights`
+    weights: Option<HeuristicWeights>,
+    /// The trained network when the agent is `ntuple`, from `--ntuple-weights`
+    ntuple: Option<NTupleAgent>,
+    /// Rollouts per move when grading move quality, 0 to leave moves ungraded
+    rollouts: u32,
+    player_name: Option<String>,
+    game_mode: GameMode,
+    coaching: CoachingMode,
+    line_mode: bool,
+    autoplay: bool,
+    /// Pause between autoplay moves
+    speed: Duration,
+    /// Position to start from instead of two random tiles
+    board: Option<Board>,
+    /// Seed for the tile spawns, so the same moves replay the same game
+    seed: Option<u64>,
+    /// Where recordings are written, from `--data-dir` or the environment
+    data_dir: DataDir,
+    /// SQLite database that gets every move and session as well
+    database: Option<String>,
+}
 
 fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("replay") {
+        if let Err(e) = replay::run(&args[1..]) {
+            println!("{}", e);
+            replay::print_usage();
+        }
+        return;
+    }
+    if args.first().map(String::as_str) == Some("puzzle") {
+        if let Err(e) = puzzle_mode::run(&args[1..]) {
+            println!("{}", e);
+            puzzle_mode::print_usage();
+        }
+        return;
+    }
+
+    // The engine checks a starting board for moves while the options are read
+    GameEngine::new();
+    let options = match parse_args(args) {
+        Ok(options) => options,
+        Err(message) => {
+            println!("{}", message);
+            print_usage();
+            return;
+        }
+    };
+
     println!("=== 2048 Human Player with AI Scoring ===");
-    println!("Use WASD keys to move:");
+    if options.autoplay {
+        println!("The {} agent plays; in line mode type the letter and press Enter:", options.agent);
+        println!("  Space/P = Pause or resume");
+        println!("  N = Step one move while paused");
+        println!("  T = Take over");
+        println!("  Q = Quit");
+        println!();
+    }
+    println!("Use the arrow keys or WASD to move:");
     println!("  W = Up");
     println!("  A = Left"); 
     println!("  S = Down");
@@ -15,81 +220,311 @@ fn main() {
     println!("  Q = Quit");
     println!();
 
-    // Initialize game engine and AI
-    GameEngine::new();
-    let mut expectimax = Expectimax::new();
-    let mut recorder = PlayerDataRecorder::new();
-    
+    // Initialize the AI
+    let mut ai: Box<dyn Agent> = match (options.agent.as_str(), options.weights, &options.ntuple) {
+        ("expectimax", Some(weights), _) => Box::new(Expectimax::with_weights(weights)),
+        ("expectimax-mt", Some(weights), _) => Box::new(ExpectimaxMultithread::with_weights(weights)),
+        (_, _, Some(ntuple)) => Box::new(ntuple.clone()),
+        (name, _, _) => agent::create(name).expect("Agent names are checked when parsing"),
+    };
+    let player_name = match options.player_name {
+        Some(name) => name,
+        // Spectators are not asked for a name
+        None if options.autoplay => String::new(),
+        None => prompt_player_name(),
+    };
+    // Games cut short last time are saved before a new one starts
+    #[cfg(feature = "sqlite")]
+    let database = match &options.database {
+        Some(path) => match Database::open(path) {
+            Ok(database) => Some(database),
+            Err(e) => {
+                println!("Error opening the database {}: {}", path, e);
+                return;
+            }
+        },
+        None => None,
+    };
+    match player_data::recover_sessions(&options.data_dir) {
+        Ok(recovered) => {
+            for session in recovered {
+                println!(
+                    "Recovered unfinished session {} ({} moves, score {})",
+                    session.session_id, session.total_moves, session.final_score
+                );
+                // Its moves went into the database as they were made
+                #[cfg(feature = "sqlite")]
+                if let Some(database) = &database {
+                    if let Err(e) = database.insert_session(&SessionRecord::from(&session)) {
+                        println!("Error adding the recovered session to the database: {}", e);
+                    }
+                }
+            }
+        }
+        Err(e) => println!("Error recovering unfinished sessions: {}", e),
+    }
+
     // Initialize game board
-    let mut board = GameEngine::insert_random_tile(0);
-    board = GameEngine::insert_random_tile(board);
+    let mut rng = match options.seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_rng(rand::thread_rng()).unwrap(),
+    };
+    let mut board = match options.board {
+        Some(board) => board,
+        None => {
+            let board = GameEngine::insert_random_tile_with(0, &mut rng);
+            GameEngine::insert_random_tile_with(board, &mut rng)
+        }
+    };
+    let mut recorder = PlayerDataRecorder::new()
+        .with_player_name(&player_name)
+        .with_game_mode(options.game_mode)
+        .with_coaching(options.coaching)
+        .with_start(board, options.seed)
+        .with_data_dir(options.data_dir.clone());
+    recorder = match options.rollouts {
+        0 => recorder.without_move_quality(),
+        rollouts => recorder.with_move_quality(MoveQualityAnalyzer::new(rollouts)),
+    };
+    #[cfg(feature = "sqlite")]
+    if let Some(database) = database {
+        recorder = recorder.with_store(Replicated::new(FileStore::new(options.data_dir.clone()), database));
+    }
+    if let Err(e) = recorder.begin() {
+        println!("Error starting the recording, moves are saved when the game ends: {}", e);
+    }
     
     let mut move_number = 1;
     
     println!("Session ID: {}", recorder.get_session_id());
-    println!("Starting game...\n");
+    println!("Game mode: {}", options.game_mode);
+    if options.data_dir != DataDir::default() {
+        println!("Recording to {}", options.data_dir.root().display());
+    }
+    if options.board.is_some() {
+        println!("Starting from the given board");
+    }
+    if let Some(seed) = options.seed {
+        println!("Tile spawns drawn from seed {}", seed);
+    }
+    match options.coaching {
+        CoachingMode::Full => {}
+        CoachingMode::Blind => println!("Coaching: blind, the AI analysis stays hidden"),
+        CoachingMode::Hints => println!("Coaching: hints, press H to see the AI analysis"),
+        CoachingMode::Feedback => println!("Coaching: feedback, the AI analysis is shown after each move"),
+    }
+    if options.game_mode == GameMode::CountDown {
+        println!("You have {} seconds; when time runs out the score is the sum of your tiles.", timer::COUNTDOWN_SECONDS);
+    }
+    println!("Starting game...");
+
+    let mut ui = create_frontend(options.line_mode);
+    let mut timer = GameTimer::new(options.game_mode);
+    let mut time_up = false;
+    let mut quit = false;
+    let mut control = if options.autoplay {
+        recorder.set_agent(Some(ai.name()));
+        Control::Agent { paused: false }
+    } else {
+        Control::Player
+    };
+    // AI scores of the position on screen and how long they took, kept when the position is
+    // shown again
+    let mut scored: Option<(Board, [Option<f64>; 4], Duration)> = None;
+    // When the position on screen was first drawn, and whether the player asked for a hint on it
+    let mut shown_since: Option<(Board, Instant)> = None;
+    let mut hinted = false;
 
     // Main game loop
     while !GameEngine::is_game_over(board) {
-        // Display current board
-        display_game_state(board, move_number);
-        
-        // Get AI scores for all possible moves
-        let move_scores = get_all_move_scores(&mut expectimax, board);
-        
-        // Display AI analysis
-        display_ai_analysis(&move_scores);
-        
-        // Get player input with timing
-        let start_time = Instant::now();
-        match get_player_move() {
-            Some(player_move) => {
-                let time_taken = start_time.elapsed();
-                
-                // Check if the move is valid
-                let new_board = GameEngine::shift(board, player_move);
-                if new_board == board {
-                    println!("Invalid move! Try again.");
+        // Get AI scores for all possible moves; engine time is not charged to the player's clock
+        timer.pause();
+        let (move_scores, scoring_time) = match scored {
+            Some((scored_board, scores, scoring_time)) if scored_board == board => (scores, scoring_time),
+            _ => {
+                let scoring_start = Instant::now();
+                let scores = ai.score_moves(board);
+                (scores, scoring_start.elapsed())
+            }
+        };
+        scored = Some((board, move_scores, scoring_time));
+        timer.resume();
+
+        // Display current board, and the AI analysis unless the coaching mode holds it back
+        let analysis = ai_analysis_lines(ai.name(), board, &move_scores);
+        let panel_visible = match control {
+            Control::Player => options.coaching == CoachingMode::Full || hinted,
+            Control::Agent { .. } => true,
+        };
+        let turn = Turn {
+            board,
+            status: format!("Move #{} | Score: {}", move_number, GameEngine::get_score(board)),
+            analysis: if panel_visible { analysis.clone() } else { Vec::new() },
+            hints: control == Control::Player && options.coaching == CoachingMode::Hints && !hinted,
+            autoplay: match control {
+                Control::Player => None,
+                Control::Agent { paused: false } => Some(format!("{} is playing", ai.name())),
+                Control::Agent { paused: true } => Some(format!("{} is paused", ai.name())),
+            },
+        };
+        ui.show(&turn, &timer);
+
+        // Get the move; the player's is timed from the moment the position was first on screen,
+        // hints and invalid moves included, and the agent's by how long it took to choose
+        let start_time = match shown_since {
+            Some((shown_board, start_time)) if shown_board == board => start_time,
+            _ => Instant::now(),
+        };
+        shown_since = Some((board, start_time));
+        let (chosen_move, time_taken) = match control {
+            Control::Player => match ui.next_input(&timer) {
+                PlayerInput::Move(player_move) => (player_move, start_time.elapsed()),
+                PlayerInput::Hint if options.coaching == CoachingMode::Hints => {
+                    if !hinted {
+                        hinted = true;
+                        recorder.request_hint();
+                    }
                     continue;
                 }
-                
-                // Record the move data
-                recorder.record_move(
-                    board,
-                    player_move,
-                    time_taken.as_millis() as u64,
-                    move_scores,
-                    GameEngine::get_score(board),
-                    move_number,
-                );
-                
-                // Make the move
-                board = GameEngine::make_move(board, player_move);
-                move_number += 1;
-                
-                println!("Move made in {:.2}s\n", time_taken.as_secs_f64());
+                PlayerInput::Hint => {
+                    ui.notify("Hints are only available with --coaching hints.");
+                    continue;
+                }
+                PlayerInput::Quit => {
+                    quit = true;
+                    break;
+                }
+                PlayerInput::TimeUp => {
+                    time_up = true;
+                    break;
+                }
+            },
+            Control::Agent { paused } => {
+                let wait = if paused { None } else { Some(options.speed) };
+                match ui.spectator_input(&timer, wait) {
+                    None | Some(SpectatorInput::Step) => {
+                        // The agent chooses from the scores shown rather than searching again;
+                        // a random agent still need not play the best of them. Its time is the
+                        // search that produced the scores.
+                        let choose_start = Instant::now();
+                        let agent_move = ai.choose_from_scores(&move_scores).expect("The game is not over");
+                        ui.notify(&format!("{} plays {:?}", ai.name(), agent_move));
+                        (agent_move, scoring_time + choose_start.elapsed())
+                    }
+                    Some(SpectatorInput::TogglePause) => {
+                        control = Control::Agent { paused: !paused };
+                        continue;
+                    }
+                    Some(SpectatorInput::TakeOver) => {
+                        control = Control::Player;
+                        recorder.set_agent(None);
+                        shown_since = None;
+                        ui.notify("You have control.");
+                        continue;
+                    }
+                    Some(SpectatorInput::Quit) => {
+                        quit = true;
+                        break;
+                    }
+                    Some(SpectatorInput::TimeUp) => {
+                        time_up = true;
+                        break;
+                    }
+                }
             }
-            None => {
-                println!("Quitting game...");
-                break;
+        };
+
+        // Check if the move is valid
+        let new_board = GameEngine::shift(board, chosen_move);
+        if new_board == board {
+            ui.notify("Invalid move! Try again.");
+            continue;
+        }
+
+        // Record the move data, which may play rollouts to grade it
+        timer.pause();
+        let recorded = recorder.record_move(
+            board,
+            chosen_move,
+            time_taken.as_millis() as u64,
+            move_scores,
+            GameEngine::get_score(board),
+            move_number,
+        );
+        timer.resume();
+        if let Err(e) = recorded {
+            ui.notify(&format!("Error logging the move: {}", e));
+        }
+
+        // Make the move
+        board = GameEngine::make_move_with(board, chosen_move, &mut rng);
+        move_number += 1;
+        hinted = false;
+
+        ui.notify(&format!("Move made in {:.2}s", time_taken.as_secs_f64()));
+        let feedback = match control {
+            Control::Player => options.coaching,
+            Control::Agent { .. } => CoachingMode::Full,
+        };
+        if feedback == CoachingMode::Feedback {
+            ui.notify(&format!("You played {:?}.", chosen_move));
+            for line in &analysis {
+                ui.notify(line);
             }
         }
+        if !matches!(feedback, CoachingMode::Full | CoachingMode::Feedback) {
+            continue;
+        }
+        if let Some(recorded) = recorder.last_move() {
+            match (recorded.move_grade, recorded.win_probability_loss, recorded.expected_score_loss) {
+                (Some(grade), Some(win_loss), Some(score_loss)) if grade != MoveGrade::Best => ui.notify(&format!(
+                    "Move quality: {} (win chance -{:.1}%, expected score -{:.0})",
+                    grade,
+                    win_loss * 100.0,
+                    score_loss
+                )),
+                (Some(grade), _, _) => ui.notify(&format!("Move quality: {}", grade)),
+                _ => {}
+            }
+        }
+    }
+
+    // Restore the terminal before printing the summary
+    drop(ui);
+    if quit {
+        println!("Quitting game...");
+    } else if time_up {
+        println!("\nTime's up!");
     }
 
-    // Game over
-    let final_score = GameEngine::get_score(board);
+    // Game over; a countdown that runs out scores the sum of the tiles, as on the web
+    let final_score = if time_up {
+        timer::time_up_score(board)
+    } else {
+        GameEngine::get_score(board)
+    };
     let highest_tile = GameEngine::get_highest_tile_val(board);
     
     println!("\n=== GAME OVER ===");
     println!("Final Score: {}", final_score);
     println!("Highest Tile: {}", highest_tile);
     println!("Total Moves: {}", move_number - 1);
+    if options.game_mode != GameMode::Normal {
+        println!("Game Time: {}s", timer.elapsed().as_secs());
+    }
     
     display_game_state(board, move_number);
     
     // Save session data
     match recorder.save_session_data(final_score, highest_tile) {
-        Ok(()) => println!("Game data saved successfully!"),
+        Ok(()) => {
+            println!("Game data saved successfully!");
+            println!(
+                "Data saved to {} and {}",
+                options.data_dir.cli_moves(recorder.get_session_id()).display(),
+                options.data_dir.cli_summary().display()
+            );
+        }
         Err(e) => println!("Error saving game data: {}", e),
     }
 }
@@ -99,127 +534,208 @@ fn display_game_state(board: Board, move_number: u32) {
     println!("{}", GameEngine::to_str(board));
 }
 
-fn get_all_move_scores(expectimax: &mut Expectimax, board: Board) -> [f64; 4] {
-    let mut scores = [0.0; 4];
-    let moves = [Move::Up, Move::Down, Move::Left, Move::Right];
-    
-    for (i, &direction) in moves.iter().enumerate() {
-        let new_board = GameEngine::shift(board, direction);
-        if new_board != board {
-            // Use a simplified expectimax for faster response
-            let mut temp_expectimax = Expectimax::new();
-            scores[i] = get_move_score(&mut temp_expectimax, new_board);
-        } else {
-            scores[i] = -1.0; // Invalid move
-        }
-    }
-    
-    scores
-}
-
-fn get_move_score(expectimax: &mut Expectimax, board: Board) -> f64 {
-    // Use a shallower depth for real-time response
-    // Simplified scoring - use game score + empty tiles heuristic
-    let game_score = GameEngine::get_score(board) as f64;
-    let empty_tiles = GameEngine::count_empty(board) as f64;
-    let smoothness = calculate_smoothness(board);
-    
-    // Simple heuristic: prioritize score, empty tiles, and board smoothness
-    game_score + (empty_tiles * 100.0) + smoothness
-}
+fn parse_args(args: Vec<String>) -> Result<Options, String> {
+    let mut options = Options {
+        agent: String::from("expectimax"),
+        weights: None,
+        ntuple: None,
+        rollouts: 200,
+        player_name: None,
+        game_mode: GameMode::Normal,
+        coaching: CoachingMode::Full,
+        line_mode: false,
+        autoplay: false,
+        speed: Duration::from_millis(500),
+        board: None,
+        seed: None,
+        data_dir: DataDir::resolve(None),
+        database: None,
+    };
 
-fn calculate_smoothness(board: Board) -> f64 {
-    let mut smoothness = 0.0;
-    let board_vec = GameEngine::to_vec(board);
-    
-    // Check horizontal smoothness
-    for row in 0..4 {
-        for col in 0..3 {
-            let idx1 = row * 4 + col;
-            let idx2 = row * 4 + col + 1;
-            if board_vec[idx1] != 0 && board_vec[idx2] != 0 {
-                smoothness -= (board_vec[idx1] as i32 - board_vec[idx2] as i32).abs() as f64;
+    let mut ntuple_weights = None;
+    let mut idx = 0;
+    while idx < args.len() {
+        match args[idx].as_str() {
+            "--line-mode" => {
+                options.line_mode = true;
+                idx += 1;
+                continue;
             }
+            "--autoplay" => {
+                options.autoplay = true;
+                idx += 1;
+                continue;
+            }
+            _ => {}
         }
-    }
-    
-    // Check vertical smoothness
-    for row in 0..3 {
-        for col in 0..4 {
-            let idx1 = row * 4 + col;
-            let idx2 = (row + 1) * 4 + col;
-            if board_vec[idx1] != 0 && board_vec[idx2] != 0 {
-                smoothness -= (board_vec[idx1] as i32 - board_vec[idx2] as i32).abs() as f64;
+        let value = args.get(idx + 1).ok_or_else(|| format!("Missing value for {}", args[idx]));
+        match args[idx].as_str() {
+            "--agent" => {
+                let name = value?;
+                if !AGENT_NAMES.contains(&name.as_str()) {
+                    return Err(format!("Unknown agent: {}", name));
+                }
+                options.agent = name.clone();
             }
+            "--weights" => options.weights = Some(HeuristicWeights::load(value?).map_err(|e| e.to_string())?),
+            "--ntuple-weights" => ntuple_weights = Some(value?.clone()),
+            "--rollouts" => {
+                options.rollouts = value?
+                    .parse()
+                    .map_err(|_| String::from("--rollouts must be a number"))?;
+            }
+            "--player" => options.player_name = Some(value?.clone()),
+            "--speed" => {
+                let millis = value?
+                    .parse()
+                    .map_err(|_| String::from("--speed must be a number of milliseconds"))?;
+                options.speed = Duration::from_millis(millis);
+            }
+            "--board" => options.board = Some(GameEngine::parse_position(value?)?),
+            "--seed" => {
+                options.seed = Some(value?.parse().map_err(|_| String::from("--seed must be a number"))?);
+            }
+            "--data-dir" => options.data_dir = DataDir::resolve(Some(value?)),
+            "--database" if cfg!(feature = "sqlite") => options.database = Some(value?.clone()),
+            "--database" => return Err(String::from("--database needs a build with the sqlite feature")),
+            "--mode" => options.game_mode = value?.parse()?,
+            "--coaching" => options.coaching = value?.parse()?,
+            other => return Err(format!("Unknown option: {}", other)),
         }
+        idx += 2;
     }
-    
-    smoothness
+    if options.weights.is_some() && !options.agent.starts_with("expectimax") {
+        return Err(format!("--weights only applies to the expectimax agents, not {}", options.agent));
+    }
+    if options.agent == "ntuple" {
+        let path = ntuple_weights.as_deref().unwrap_or(ntuple::DEFAULT_WEIGHTS_FILE);
+        options.ntuple = Some(NTupleAgent::load(path).map_err(|e| e.to_string())?);
+    } else if ntuple_weights.is_some() {
+        return Err(format!("--ntuple-weights only applies to the ntuple agent, not {}", options.agent));
+    }
+    Ok(options)
+}
+
+fn print_usage() {
+    println!("Usage: cargo run --release -- [options]");
+    println!("       cargo run --release -- replay FILE [options]");
+    println!("       cargo run --release -- puzzle [FILE] [options]");
+    println!("  --agent NAME    AI used to score moves: {} (default expectimax)", AGENT_NAMES.join(", "));
+    println!("  --weights PATH  Heuristic weights for the expectimax agents, as written by tune");
+    println!("  --ntuple-weights PATH");
+    println!(
+        "                  Network for the ntuple agent, as written by train_ntuple (default {})",
+        ntuple::DEFAULT_WEIGHTS_FILE
+    );
+    println!("  --rollouts N    Rollouts per move when grading move quality, 0 to skip grading (default 200)");
+    println!("  --player NAME   Player name for the recordings (asked for when left out)");
+    println!("  --mode MODE     normal, countup or countdown (default normal)");
+    println!("  --coaching MODE full, blind, hints or feedback: when the AI analysis is shown (default full)");
+    println!("  --line-mode     Type moves and press Enter instead of using the full-screen UI");
+    println!("  --autoplay      Watch the agent play; pause, step or take over at any time");
+    println!("  --speed MS      Pause between autoplay moves in milliseconds (default 500)");
+    println!("  --board BOARD   Start from a board: hex like 0x1100000000000002 or 16 tile values");
+    println!("                  row by row like \"2,2,0,0,0,0,0,0,0,0,0,0,0,0,0,4\"");
+    println!("  --seed N        Draw the new tiles from seed N so the same moves replay the same game");
+    println!("  --data-dir DIR  Where recordings are written (default ${} or the current directory)", DATA_DIR_ENV);
+    println!("  --database PATH Also record into a SQLite database (needs the sqlite feature)");
 }
 
-fn display_ai_analysis(move_scores: &[f64; 4]) {
+fn prompt_player_name() -> String {
+    print!("Player name (Enter to skip): ");
+    io::stdout().flush().unwrap();
+    let mut input = String::new();
+    io::stdin().read_line(&mut input).unwrap_or_default();
+    input.trim().to_string()
+}
+
+// Scores are in `[Up, Down, Left, Right]` order, `None` for invalid moves
+fn ai_analysis_lines(ai_name: &str, board: Board, move_scores: &[Option<f64>; 4]) -> Vec<String> {
     let moves = ["Up", "Down", "Left", "Right"];
     let mut valid_moves: Vec<(usize, f64)> = move_scores.iter()
         .enumerate()
-        .filter(|(_, &score)| score >= 0.0)
-        .map(|(i, &score)| (i, score))
+        .filter_map(|(i, score)| score.map(|score| (i, score)))
         .collect();
     
     if valid_moves.is_empty() {
-        println!("No valid moves available!");
-        return;
+        return vec![String::from("No valid moves available!")];
     }
     
     // Sort by score (descending)
-    valid_moves.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
+    valid_moves.sort_by(|a, b| b.1.total_cmp(&a.1));
     
-    // Calculate variation (difficulty)
-    let scores: Vec<f64> = valid_moves.iter().map(|(_, score)| *score).collect();
-    let mean_score = scores.iter().sum::<f64>() / scores.len() as f64;
-    let variance = scores.iter()
-        .map(|score| (score - mean_score).powi(2))
-        .sum::<f64>() / scores.len() as f64;
-    let std_dev = variance.sqrt();
-    
-    println!("AI Analysis:");
+    let mut lines = vec![format!("AI Analysis ({}):", ai_name)];
     for (rank, &(move_idx, score)) in valid_moves.iter().enumerate() {
         let marker = if rank == 0 { "★" } else { " " };
-        println!("  {}{}: {:.1} ({})", marker, moves[move_idx], score, 
-                 if rank == 0 { "BEST" } else { "    " });
+        lines.push(format!("  {}{}: {:.1} ({})", marker, moves[move_idx], score,
+                           if rank == 0 { "BEST" } else { "    " }));
     }
     
-    let difficulty = if std_dev < 50.0 { "EASY" } 
-                    else if std_dev < 200.0 { "MEDIUM" } 
-                    else { "HARD" };
-    
-    println!("  Decision difficulty: {} (variation: {:.1})", difficulty, std_dev);
-    println!();
+    let rating = difficulty::rate_position(board, move_scores);
+    lines.push(format!(
+        "  Decision difficulty: {} (gap: {:.4}%, near-optimal moves: {}, death risk: {:.0}%)",
+        rating.level,
+        rating.score_gap * 100.0,
+        rating.near_optimal_moves,
+        rating.death_risk * 100.0
+    ));
+    lines
 }
 
-fn get_player_move() -> Option<Move> {
+// The full-screen UI needs a terminal on both ends; piped games fall back to lines
+fn create_frontend(line_mode: bool) -> Box<dyn Frontend> {
+    if !line_mode && io::stdin().is_terminal() && io::stdout().is_terminal() {
+        match TerminalUi::new() {
+            Ok(ui) => return Box::new(ui),
+            Err(e) => println!("Could not start the full-screen UI ({}), reading moves line by line", e),
+        }
+    }
+    Box::new(LineUi {
+        input: spawn_input_reader(),
+        hints: false,
+    })
+}
+
+// Reads stdin on its own thread so a countdown can run out while the player thinks
+fn spawn_input_reader() -> Receiver<String> {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        for line in io::stdin().lock().lines().map_while(Result::ok) {
+            if sender.send(line).is_err() {
+                break;
+            }
+        }
+    });
+    receiver
+}
+
+fn get_player_move(input: &Receiver<String>, deadline: Option<Instant>, prompt: &str) -> PlayerInput {
     loop {
-        print!("Your move (WASD or Q to quit): ");
+        print!("{}", prompt);
         io::stdout().flush().unwrap();
         
-        let mut input = String::new();
-        match io::stdin().read_line(&mut input) {
-            Ok(_) => {
-                match input.trim().to_lowercase().as_str() {
-                    "w" => return Some(Move::Up),
-                    "a" => return Some(Move::Left),
-                    "s" => return Some(Move::Down),
-                    "d" => return Some(Move::Right),
-                    "q" | "quit" | "exit" => return None,
+        let line = match deadline {
+            Some(deadline) => input.recv_timeout(deadline.saturating_duration_since(Instant::now())),
+            None => input.recv().map_err(|_| RecvTimeoutError::Disconnected),
+        };
+        match line {
+            Ok(line) => {
+                match line.trim().to_lowercase().as_str() {
+                    "w" => return PlayerInput::Move(Move::Up),
+                    "a" => return PlayerInput::Move(Move::Left),
+                    "s" => return PlayerInput::Move(Move::Down),
+                    "d" => return PlayerInput::Move(Move::Right),
+                    "h" => return PlayerInput::Hint,
+                    "q" | "quit" | "exit" => return PlayerInput::Quit,
                     _ => {
                         println!("Invalid input! Use W/A/S/D for moves or Q to quit.");
                         continue;
                     }
                 }
             }
-            Err(_) => {
-                println!("Error reading input. Try again.");
-                continue;
-            }
+            Err(RecvTimeoutError::Timeout) => return PlayerInput::TimeUp,
+            // End of input
+            Err(RecvTimeoutError::Disconnected) => return PlayerInput::Quit,
         }
     }
 }
@@ -0,0 +1,306 @@
+use crate::agent::best_move;
+use crate::engine as GameEngine;
+use crate::engine::{Board, Move, MOVES};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::HashMap;
+
+/// Scores each move by the average final score of random games played after it.
+pub struct MonteCarlo {
+    rollouts: u32,
+    rng: StdRng,
+}
+
+impl MonteCarlo {
+    pub fn new(rollouts: u32) -> Self {
+        Self::with_rng(rollouts, StdRng::from_rng(rand::thread_rng()).unwrap())
+    }
+
+    pub fn with_seed(rollouts: u32, seed: u64) -> Self {
+        Self::with_rng(rollouts, StdRng::seed_from_u64(seed))
+    }
+
+    fn with_rng(rollouts: u32, rng: StdRng) -> Self {
+        MonteCarlo {
+            rollouts: rollouts.max(1),
+            rng,
+        }
+    }
+
+    pub fn get_next_move(&mut self, board: Board) -> Option<Move> {
+        best_move(&self.score_moves(board))
+    }
+
+    /// Average rollout score of each move in `[Up, Down, Left, Right]` order, `None` if invalid.
+    pub fn score_moves(&mut self, board: Board) -> [Option<f64>; 4] {
+        let mut scores = [None; 4];
+        for (score, &direction) in scores.iter_mut().zip(MOVES.iter()) {
+            let afterstate = GameEngine::shift(board, direction);
+            if afterstate == board {
+                continue;
+            }
+            let total: u64 = (0..self.rollouts)
+                .map(|_| {
+                    let board = GameEngine::insert_random_tile_with(afterstate, &mut self.rng);
+                    GameEngine::get_score(random_rollout(board, &mut self.rng))
+                })
+                .sum();
+            *score = Some(total as f64 / self.rollouts as f64);
+        }
+        scores
+    }
+}
+
+/// Monte Carlo tree search over move and spawn nodes, finished with random rollouts.
+pub struct Mcts {
+    iterations: u32,
+    exploration: f64,
+    rng: StdRng,
+}
+
+// A position where the player chooses a move
+struct DecisionNode {
+    visits: u32,
+    // Chance node index for each move, `None` if the move is invalid
+    children: [Option<usize>; 4],
+    expanded: bool,
+}
+
+// A position after a move, waiting for the random tile
+struct ChanceNode {
+    afterstate: Board,
+    visits: u32,
+    total_score: f64,
+    children: HashMap<Board, usize>,
+}
+
+struct SearchTree {
+    decisions: Vec<DecisionNode>,
+    chances: Vec<ChanceNode>,
+    // Largest rollout score so far, used to bring scores into the UCT range
+    score_scale: f64,
+}
+
+impl Mcts {
+    pub fn new(iterations: u32) -> Self {
+        Self::with_rng(iterations, StdRng::from_rng(rand::thread_rng()).unwrap())
+    }
+
+    pub fn with_seed(iterations: u32, seed: u64) -> Self {
+        Self::with_rng(iterations, StdRng::seed_from_u64(seed))
+    }
+
+    fn with_rng(iterations: u32, rng: StdRng) -> Self {
+        Mcts {
+            iterations: iterations.max(1),
+            exploration: std::f64::consts::SQRT_2,
+            rng,
+        }
+    }
+
+    pub fn get_next_move(&mut self, board: Board) -> Option<Move> {
+        let tree = self.search(board);
+        let root = &tree.decisions[0];
+        // The most visited move is the most robust choice
+        (0..4)
+            .filter_map(|idx| root.children[idx].map(|child| (idx, tree.chances[child].visits)))
+            .max_by_key(|&(_, visits)| visits)
+            .map(|(idx, _)| MOVES[idx])
+    }
+
+    /// Mean rollout score of each move in `[Up, Down, Left, Right]` order, `None` if invalid.
+    pub fn score_moves(&mut self, board: Board) -> [Option<f64>; 4] {
+        let tree = self.search(board);
+        let mut scores = [None; 4];
+        for (score, child) in scores.iter_mut().zip(tree.decisions[0].children.iter()) {
+            if let Some(child) = *child {
+                let node = &tree.chances[child];
+                *score = Some(node.total_score / node.visits.max(1) as f64);
+            }
+        }
+        scores
+    }
+
+    fn search(&mut self, board: Board) -> SearchTree {
+        let mut tree = SearchTree {
+            decisions: vec![DecisionNode::new()],
+            chances: Vec::new(),
+            score_scale: 1.,
+        };
+        for _ in 0..self.iterations {
+            self.iterate(&mut tree, board);
+        }
+        tree
+    }
+
+    fn iterate(&mut self, tree: &mut SearchTree, root_board: Board) {
+        let mut board = root_board;
+        let mut decision = 0;
+        let mut path = Vec::new();
+
+        // Selection and expansion: walk down until a new position is reached
+        loop {
+            if !tree.decisions[decision].expanded {
+                tree.expand(decision, board);
+                // A position reached for the first time is scored by a rollout from it
+                if decision != 0 {
+                    break;
+                }
+            }
+            let chance = match self.select(tree, decision) {
+                Some(chance) => chance,
+                None => break,
+            };
+            path.push((decision, chance));
+
+            board = GameEngine::insert_random_tile_with(tree.chances[chance].afterstate, &mut self.rng);
+            let next = match tree.chances[chance].children.get(&board) {
+                Some(&next) => next,
+                None => {
+                    let next = tree.decisions.len();
+                    tree.decisions.push(DecisionNode::new());
+                    tree.chances[chance].children.insert(board, next);
+                    next
+                }
+            };
+            decision = next;
+        }
+
+        // Simulation and backpropagation
+        let score = GameEngine::get_score(random_rollout(board, &mut self.rng)) as f64;
+        tree.score_scale = tree.score_scale.max(score);
+        tree.decisions[decision].visits += 1;
+        for (decision, chance) in path {
+            tree.decisions[decision].visits += 1;
+            tree.chances[chance].visits += 1;
+            tree.chances[chance].total_score += score;
+        }
+    }
+
+    // UCT choice among the moves of a position, `None` if it has no moves
+    fn select(&self, tree: &SearchTree, decision: usize) -> Option<usize> {
+        let node = &tree.decisions[decision];
+        let parent_visits = (node.visits.max(1) as f64).ln();
+        node.children
+            .iter()
+            .filter_map(|&child| child)
+            .map(|child| {
+                let chance = &tree.chances[child];
+                let priority = if chance.visits == 0 {
+                    f64::INFINITY
+                } else {
+                    let visits = chance.visits as f64;
+                    chance.total_score / visits / tree.score_scale
+                        + self.exploration * (parent_visits / visits).sqrt()
+                };
+                (child, priority)
+            })
+            .fold(None, |best: Option<(usize, f64)>, (child, priority)| match best {
+                Some((_, best_priority)) if best_priority >= priority => best,
+                _ => Some((child, priority)),
+            })
+            .map(|(child, _)| child)
+    }
+}
+
+impl DecisionNode {
+    fn new() -> Self {
+        DecisionNode {
+            visits: 0,
+            children: [None; 4],
+            expanded: false,
+        }
+    }
+}
+
+impl SearchTree {
+    fn expand(&mut self, decision: usize, board: Board) {
+        for (idx, &direction) in MOVES.iter().enumerate() {
+            let afterstate = GameEngine::shift(board, direction);
+            if afterstate != board {
+                self.decisions[decision].children[idx] = Some(self.chances.len());
+                self.chances.push(ChanceNode {
+                    afterstate,
+                    visits: 0,
+                    total_score: 0.,
+                    children: HashMap::new(),
+                });
+            }
+        }
+        self.decisions[decision].expanded = true;
+    }
+}
+
+/// Plays uniformly random valid moves until the game is over and returns the final board.
+pub fn random_rollout<R: Rng + ?Sized>(mut board: Board, rng: &mut R) -> Board {
+    while let Some(afterstate) = random_afterstate(board, rng) {
+        board = GameEngine::insert_random_tile_with(afterstate, rng);
+    }
+    board
+}
+
+// The board after a valid move picked with equal probability, `None` once the game is over
+fn random_afterstate<R: Rng + ?Sized>(board: Board, rng: &mut R) -> Option<Board> {
+    let mut afterstates = [0; 4];
+    let mut valid = 0;
+    for &direction in &MOVES {
+        let new_board = GameEngine::shift(board, direction);
+        if new_board != board {
+            afterstates[valid] = new_board;
+            valid += 1;
+        }
+    }
+    if valid == 0 {
+        None
+    } else {
+        Some(afterstates[rng.gen_range(0, valid)])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_rolls_out_valid_moves_evenly() {
+        GameEngine::new();
+        // Up, Down and Left move the tile at the end of the second row; Right does not
+        let board = 0x0000_0001_0000_0000;
+        let mut rng = StdRng::seed_from_u64(3);
+        let mut counts = std::collections::HashMap::new();
+        for _ in 0..3000 {
+            *counts.entry(random_afterstate(board, &mut rng).unwrap()).or_insert(0) += 1;
+        }
+        assert_eq!(counts.len(), 3);
+        assert!(counts.values().all(|&count| (850..1150).contains(&count)), "{:?}", counts);
+        assert_eq!(random_afterstate(0x1234432112344321, &mut rng), None);
+    }
+
+    #[test]
+    fn it_scores_only_valid_moves() {
+        GameEngine::new();
+        // Only Up and Right change this board
+        let scores = MonteCarlo::with_seed(5, 1).score_moves(0x0000000000001200);
+        assert_eq!(scores[1], None);
+        assert_eq!(scores[2], None);
+        assert!(scores[0].is_some() && scores[3].is_some());
+    }
+
+    #[test]
+    fn it_has_no_move_on_a_lost_board() {
+        GameEngine::new();
+        let board = 0x1234432112344321;
+        assert!(MonteCarlo::with_seed(5, 1).get_next_move(board).is_none());
+        assert!(Mcts::with_seed(20, 1).get_next_move(board).is_none());
+    }
+
+    #[test]
+    fn it_searches_chance_nodes() {
+        GameEngine::new();
+        let mut mcts = Mcts::with_seed(200, 3);
+        let tree = mcts.search(0x1100000000000000);
+        assert_eq!(tree.decisions[0].visits, 200);
+        assert!(tree.chances.iter().any(|chance| chance.children.len() > 1));
+        assert!(mcts.get_next_move(0x1100000000000000).is_some());
+    }
+}
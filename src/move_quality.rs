@@ -0,0 +1,279 @@
+use crate::agent::{Agent, CornerAgent};
+use crate::engine as GameEngine;
+use crate::engine::{Board, Move, MOVES};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MoveGrade {
+    Best,
+    Good,
+    Inaccuracy,
+    Mistake,
+    Blunder,
+}
+
+impl fmt::Display for MoveGrade {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let label = match self {
+            MoveGrade::Best => "best",
+            MoveGrade::Good => "good",
+            MoveGrade::Inaccuracy => "inaccuracy",
+            MoveGrade::Mistake => "mistake",
+            MoveGrade::Blunder => "blunder",
+        };
+        write!(f, "{}", label)
+    }
+}
+
+/// Largest loss for each grade; anything beyond `mistake` is a blunder.
+///
+/// A move's loss is the win probability it gives up, or the share of the best move's expected
+/// future points it gives up if that is larger.
+#[derive(Debug, Clone, Copy)]
+pub struct QualityThresholds {
+    pub good: f64,
+    pub inaccuracy: f64,
+    pub mistake: f64,
+}
+
+impl Default for QualityThresholds {
+    fn default() -> Self {
+        QualityThresholds {
+            good: 0.02,
+            inaccuracy: 0.05,
+            mistake: 0.15,
+        }
+    }
+}
+
+impl QualityThresholds {
+    /// Thresholds for `grade_by_scores`, where the loss is the share of the best move's score
+    /// given up. Expectimax scores moves a median 0.16% apart, so a move more than 1% behind is
+    /// a mistake and one more than 5% behind a blunder.
+    pub fn for_scores() -> Self {
+        QualityThresholds {
+            good: 0.002,
+            inaccuracy: 0.01,
+            mistake: 0.05,
+        }
+    }
+
+    /// Grades a move that is not the best by its loss.
+    pub fn grade(&self, loss: f64) -> MoveGrade {
+        if loss <= self.good {
+            MoveGrade::Good
+        } else if loss <= self.inaccuracy {
+            MoveGrade::Inaccuracy
+        } else if loss <= self.mistake {
+            MoveGrade::Mistake
+        } else {
+            MoveGrade::Blunder
+        }
+    }
+}
+
+/// Grades `chosen` by an agent's scores in `[Up, Down, Left, Right]` order, so the move the
+/// agent ranks first is always graded best. `None` if `chosen` is invalid.
+pub fn grade_by_scores(scores: &[Option<f64>; 4], chosen: Move, thresholds: &QualityThresholds) -> Option<MoveGrade> {
+    let chosen_score = scores[MOVES.iter().position(|&direction| direction == chosen).unwrap()]?;
+    let best = scores.iter().flatten().copied().fold(f64::NEG_INFINITY, f64::max);
+    if chosen_score >= best {
+        return Some(MoveGrade::Best);
+    }
+    Some(thresholds.grade((best - chosen_score) / best.abs().max(f64::EPSILON)))
+}
+
+/// Rollout estimates for each move in `[Up, Down, Left, Right]` order, `None` if invalid.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MoveEstimates {
+    pub win_probability: [Option<f64>; 4],
+    /// Average final score of the rollouts
+    pub expected_score: [Option<f64>; 4],
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct MoveQuality {
+    pub grade: MoveGrade,
+    pub best_move: Move,
+    pub win_probability: f64,
+    pub win_probability_loss: f64,
+    pub expected_score_loss: f64,
+}
+
+/// Judges moves by playing rollouts after each of them and comparing the outcomes.
+///
+/// A rollout wins when it makes `target_tile`, by default the tile after the highest on the
+/// board. Rollouts follow the corner strategy so that they make progress the way a player
+/// would, and every move is rolled out with the same spawns to keep the comparison fair.
+pub struct MoveQualityAnalyzer {
+    rollouts: u32,
+    target_tile: Option<u64>,
+    thresholds: QualityThresholds,
+    rng: StdRng,
+}
+
+impl MoveQualityAnalyzer {
+    pub fn new(rollouts: u32) -> Self {
+        Self::with_rng(rollouts, StdRng::from_rng(rand::thread_rng()).unwrap())
+    }
+
+    pub fn with_seed(rollouts: u32, seed: u64) -> Self {
+        Self::with_rng(rollouts, StdRng::seed_from_u64(seed))
+    }
+
+    fn with_rng(rollouts: u32, rng: StdRng) -> Self {
+        MoveQualityAnalyzer {
+            rollouts: rollouts.max(1),
+            target_tile: None,
+            thresholds: QualityThresholds::default(),
+            rng,
+        }
+    }
+
+    pub fn with_target_tile(mut self, target_tile: u64) -> Self {
+        self.target_tile = Some(target_tile);
+        self
+    }
+
+    pub fn with_thresholds(mut self, thresholds: QualityThresholds) -> Self {
+        self.thresholds = thresholds;
+        self
+    }
+
+    pub fn estimate_moves(&mut self, board: Board) -> MoveEstimates {
+        let target = self
+            .target_tile
+            .unwrap_or_else(|| GameEngine::get_highest_tile_val(board) * 2)
+            .max(4);
+        let seed = self.rng.gen::<u64>();
+        let mut estimates = MoveEstimates {
+            win_probability: [None; 4],
+            expected_score: [None; 4],
+        };
+        for (idx, &direction) in MOVES.iter().enumerate() {
+            let afterstate = GameEngine::shift(board, direction);
+            if afterstate == board {
+                continue;
+            }
+            let mut wins = 0;
+            let mut total_score = 0;
+            for rollout in 0..self.rollouts {
+                let mut rng = StdRng::seed_from_u64(seed.wrapping_add(rollout as u64));
+                let (final_board, won) = corner_rollout(afterstate, target, &mut rng);
+                wins += won as u32;
+                total_score += GameEngine::get_score(final_board);
+            }
+            estimates.win_probability[idx] = Some(wins as f64 / self.rollouts as f64);
+            estimates.expected_score[idx] = Some(total_score as f64 / self.rollouts as f64);
+        }
+        estimates
+    }
+
+    /// Grades `chosen` against the other moves from `board`, `None` if it is not a valid move.
+    pub fn assess(&mut self, board: Board, chosen: Move) -> Option<MoveQuality> {
+        let estimates = self.estimate_moves(board);
+        let chosen_idx = MOVES.iter().position(|&direction| direction == chosen).unwrap();
+        let win_probability = estimates.win_probability[chosen_idx]?;
+        let expected_score = estimates.expected_score[chosen_idx]?;
+
+        // Rank by win probability, then by expected score, preferring the earliest on ties
+        let mut best: Option<(usize, f64)> = None;
+        for idx in 0..4 {
+            if let (Some(win), Some(score)) = (estimates.win_probability[idx], estimates.expected_score[idx]) {
+                if best.is_none_or(|(best_idx, best_win)| {
+                    (win, score) > (best_win, estimates.expected_score[best_idx].unwrap())
+                }) {
+                    best = Some((idx, win));
+                }
+            }
+        }
+        let (best_idx, best_win) = best?;
+        let best_score = estimates.expected_score.iter().flatten().fold(0., |best: f64, &s| best.max(s));
+        let win_probability_loss = best_win - win_probability;
+        let expected_score_loss = best_score - expected_score;
+
+        // Share of the points still to come that the move gives up
+        let future_points = best_score - GameEngine::get_score(board) as f64;
+        let score_share_loss = if future_points > 0. {
+            expected_score_loss / future_points
+        } else {
+            0.
+        };
+        let loss = win_probability_loss.max(score_share_loss);
+
+        let grade = if chosen_idx == best_idx || loss <= 0. {
+            MoveGrade::Best
+        } else {
+            self.thresholds.grade(loss)
+        };
+
+        Some(MoveQuality {
+            grade,
+            best_move: MOVES[best_idx],
+            win_probability,
+            win_probability_loss,
+            expected_score_loss,
+        })
+    }
+}
+
+// Plays the corner strategy to the end of the game, noting whether `target` appeared
+fn corner_rollout<R: Rng + ?Sized>(mut board: Board, target: u64, rng: &mut R) -> (Board, bool) {
+    let mut won = false;
+    loop {
+        board = GameEngine::insert_random_tile_with(board, rng);
+        won = won || GameEngine::get_highest_tile_val(board) >= target;
+        match CornerAgent.choose(board) {
+            Some(direction) => board = GameEngine::shift(board, direction),
+            None => return (board, won),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_grades_by_loss() {
+        let thresholds = QualityThresholds::default();
+        assert_eq!(thresholds.grade(0.01), MoveGrade::Good);
+        assert_eq!(thresholds.grade(0.04), MoveGrade::Inaccuracy);
+        assert_eq!(thresholds.grade(0.1), MoveGrade::Mistake);
+        assert_eq!(thresholds.grade(0.5), MoveGrade::Blunder);
+        assert!(MoveGrade::Best < MoveGrade::Blunder);
+    }
+
+    #[test]
+    fn it_grades_by_the_agents_scores() {
+        let thresholds = QualityThresholds::for_scores();
+        let scores = [Some(1000.), None, Some(995.), Some(900.)];
+        assert_eq!(grade_by_scores(&scores, Move::Up, &thresholds), Some(MoveGrade::Best));
+        assert_eq!(grade_by_scores(&scores, Move::Left, &thresholds), Some(MoveGrade::Inaccuracy));
+        assert_eq!(grade_by_scores(&scores, Move::Right, &thresholds), Some(MoveGrade::Blunder));
+        assert_eq!(grade_by_scores(&scores, Move::Down, &thresholds), None);
+    }
+
+    #[test]
+    fn it_assesses_moves_with_rollouts() {
+        GameEngine::new();
+        let mut analyzer = MoveQualityAnalyzer::with_seed(50, 3);
+        // Left or Right merge the 2s straight into the target
+        let board = 0x1100000000000000;
+        let quality = analyzer.assess(board, Move::Left).unwrap();
+        assert_eq!(quality.grade, MoveGrade::Best);
+        assert_eq!(quality.win_probability, 1.);
+
+        // After Left the only empty cell is bottom right, and most spawns there end the game
+        let risky = 0x1234432112340432;
+        let quality = analyzer.assess(risky, Move::Left).unwrap();
+        assert_ne!(quality.best_move, Move::Left);
+        assert!(quality.expected_score_loss > 0.);
+        assert!(quality.grade >= MoveGrade::Mistake);
+        assert!(analyzer.assess(risky, Move::Right).is_none());
+    }
+}
@@ -0,0 +1,325 @@
+use crate::engine as GameEngine;
+use crate::engine::{Board, Move};
+use crate::expectimax::Evaluator;
+use rand::Rng;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+const MAGIC: &[u8; 4] = b"NTUP";
+// Version 2 added the number of games trained on
+const VERSION: u32 = 2;
+
+/// Where `train_ntuple` saves its weights and the `ntuple` agent looks for them by default.
+pub const DEFAULT_WEIGHTS_FILE: &str = "ntuple_weights.bin";
+
+/// Cells of a tuple, indexed like `engine::to_vec` (row-major, top left first).
+pub type TupleShape = Vec<usize>;
+
+/// An n-tuple network valuing afterstates (boards just after a move, before the spawn).
+///
+/// Each tuple is looked up in all 8 rotations and reflections of the board and shares one
+/// weight table between them.
+pub struct NTupleNetwork {
+    shapes: Vec<TupleShape>,
+    // For each shape, the cells of its 8 symmetric copies
+    symmetries: Vec<Vec<TupleShape>>,
+    weights: Vec<Vec<f32>>,
+    // Self-play games trained on so far, so that training picks up where it stopped
+    games_trained: u64,
+}
+
+impl NTupleNetwork {
+    pub fn new(shapes: Vec<TupleShape>) -> Self {
+        for shape in &shapes {
+            assert!(!shape.is_empty() && shape.len() <= 6, "Tuples must have 1 to 6 cells");
+            assert!(shape.iter().all(|&cell| cell < 16), "Tuple cells must be within the board");
+        }
+        let symmetries = shapes.iter().map(|shape| symmetric_shapes(shape)).collect();
+        let weights = shapes.iter().map(|shape| vec![0.; 1 << (4 * shape.len())]).collect();
+        NTupleNetwork {
+            shapes,
+            symmetries,
+            weights,
+            games_trained: 0,
+        }
+    }
+
+    /// Two straight lines and three squares, each covering 4 cells.
+    pub fn default_shapes() -> Vec<TupleShape> {
+        vec![
+            vec![0, 1, 2, 3],
+            vec![4, 5, 6, 7],
+            vec![0, 1, 4, 5],
+            vec![1, 2, 5, 6],
+            vec![5, 6, 9, 10],
+        ]
+    }
+
+    pub fn shapes(&self) -> &[TupleShape] {
+        &self.shapes
+    }
+
+    pub fn games_trained(&self) -> u64 {
+        self.games_trained
+    }
+
+    /// Expected score still to be gained from an afterstate.
+    pub fn value(&self, board: Board) -> f64 {
+        let mut value = 0.;
+        for (symmetries, weights) in self.symmetries.iter().zip(&self.weights) {
+            for cells in symmetries {
+                value += weights[tuple_index(board, cells)] as f64;
+            }
+        }
+        value
+    }
+
+    /// Moves the value of an afterstate `learning_rate` of the way towards `target`, spreading
+    /// the step evenly over all lookups.
+    pub fn update(&mut self, board: Board, target: f64, learning_rate: f64) {
+        let lookups = self.symmetries.iter().map(Vec::len).sum::<usize>();
+        let error = (target - self.value(board)) * learning_rate / lookups as f64;
+        for (symmetries, weights) in self.symmetries.iter().zip(self.weights.iter_mut()) {
+            for cells in symmetries {
+                weights[tuple_index(board, cells)] += error as f32;
+            }
+        }
+    }
+
+    /// Picks the move maximising reward plus afterstate value.
+    ///
+    /// Returns the move with its afterstate and reward, or `None` if no move changes the board.
+    pub fn best_move(&self, board: Board) -> Option<(Move, Board, u64)> {
+        let current_score = GameEngine::get_score(board);
+        let mut best = None;
+        let mut best_value = f64::NEG_INFINITY;
+        for &direction in &[Move::Up, Move::Down, Move::Left, Move::Right] {
+            let afterstate = GameEngine::shift(board, direction);
+            if afterstate == board {
+                continue;
+            }
+            let reward = GameEngine::get_score(afterstate) - current_score;
+            let value = reward as f64 + self.value(afterstate);
+            if value > best_value {
+                best_value = value;
+                best = Some((direction, afterstate, reward));
+            }
+        }
+        best
+    }
+
+    /// Plays one game, learning from every move with TD(0) on afterstates.
+    ///
+    /// Returns the final board.
+    pub fn train_game<R: Rng + ?Sized>(&mut self, learning_rate: f64, rng: &mut R) -> Board {
+        let mut board = GameEngine::insert_random_tile_with(0, rng);
+        board = GameEngine::insert_random_tile_with(board, rng);
+        let mut previous_afterstate = None;
+
+        while let Some((_, afterstate, reward)) = self.best_move(board) {
+            if let Some(previous) = previous_afterstate {
+                let target = reward as f64 + self.value(afterstate);
+                self.update(previous, target, learning_rate);
+            }
+            previous_afterstate = Some(afterstate);
+            board = GameEngine::insert_random_tile_with(afterstate, rng);
+        }
+
+        // Nothing more can be gained after the last move
+        if let Some(previous) = previous_afterstate {
+            self.update(previous, 0., learning_rate);
+        }
+        self.games_trained += 1;
+        board
+    }
+
+    /// Writes the weights to `path`, through a temporary file that is renamed over it so that an
+    /// interrupted save never leaves a truncated file.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let path = path.as_ref();
+        let mut tmp_path = path.as_os_str().to_owned();
+        tmp_path.push(".tmp");
+        self.write(BufWriter::new(File::create(&tmp_path)?))?;
+        std::fs::rename(&tmp_path, path)
+    }
+
+    fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&VERSION.to_le_bytes())?;
+        writer.write_all(&self.games_trained.to_le_bytes())?;
+        writer.write_all(&(self.shapes.len() as u32).to_le_bytes())?;
+        for shape in &self.shapes {
+            writer.write_all(&(shape.len() as u32).to_le_bytes())?;
+            for &cell in shape {
+                writer.write_all(&[cell as u8])?;
+            }
+        }
+        for weights in &self.weights {
+            for weight in weights {
+                writer.write_all(&weight.to_le_bytes())?;
+            }
+        }
+        writer.flush()
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid_data("Not an n-tuple weights file"));
+        }
+        if read_u32(&mut reader)? != VERSION {
+            return Err(invalid_data("Unsupported n-tuple weights version"));
+        }
+        let mut bytes = [0; 8];
+        reader.read_exact(&mut bytes)?;
+        let games_trained = u64::from_le_bytes(bytes);
+
+        let num_shapes = read_u32(&mut reader)?;
+        let mut shapes = Vec::new();
+        for _ in 0..num_shapes {
+            let len = read_u32(&mut reader)? as usize;
+            if len == 0 || len > 6 {
+                return Err(invalid_data("Invalid tuple length"));
+            }
+            let mut cells = vec![0; len];
+            reader.read_exact(&mut cells)?;
+            if cells.iter().any(|&cell| cell >= 16) {
+                return Err(invalid_data("Invalid tuple cell"));
+            }
+            shapes.push(cells.into_iter().map(|cell| cell as usize).collect());
+        }
+
+        let mut network = NTupleNetwork::new(shapes);
+        network.games_trained = games_trained;
+        let mut bytes = [0; 4];
+        for weights in network.weights.iter_mut() {
+            for weight in weights.iter_mut() {
+                reader.read_exact(&mut bytes)?;
+                *weight = f32::from_le_bytes(bytes);
+            }
+        }
+        Ok(network)
+    }
+}
+
+impl Default for NTupleNetwork {
+    fn default() -> Self {
+        Self::new(Self::default_shapes())
+    }
+}
+
+/// Leaf evaluation for expectimax: the score so far plus the score still expected.
+impl Evaluator for NTupleNetwork {
+    fn evaluate(&self, board: Board) -> f64 {
+        GameEngine::get_score(board) as f64 + self.value(board)
+    }
+}
+
+fn tuple_index(board: Board, cells: &[usize]) -> usize {
+    cells.iter().fold(0, |index, &cell| {
+        (index << 4) | ((board >> (60 - 4 * cell)) & 0xf) as usize
+    })
+}
+
+fn symmetric_shapes(shape: &[usize]) -> Vec<TupleShape> {
+    let mut shapes = Vec::with_capacity(8);
+    for &mirror in &[false, true] {
+        for rotations in 0..4 {
+            shapes.push(
+                shape
+                    .iter()
+                    .map(|&cell| {
+                        let (mut row, mut col) = (cell / 4, cell % 4);
+                        if mirror {
+                            col = 3 - col;
+                        }
+                        for _ in 0..rotations {
+                            let rotated = (col, 3 - row);
+                            row = rotated.0;
+                            col = rotated.1;
+                        }
+                        row * 4 + col
+                    })
+                    .collect(),
+            );
+        }
+    }
+    shapes
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn it_values_symmetric_boards_equally() {
+        GameEngine::new();
+        let mut network = NTupleNetwork::default();
+        network.update(0x1230004500000000, 100., 0.1);
+        let value = network.value(0x1230004500000000);
+        assert!(value > 0.);
+        assert!((network.value(GameEngine::transpose(0x1230004500000000)) - value).abs() < 1e-6);
+    }
+
+    #[test]
+    fn it_steps_the_value_by_the_learning_rate() {
+        GameEngine::new();
+        // With every tile different no two lookups share a weight
+        let mut network = NTupleNetwork::new(vec![vec![0, 1], vec![5, 6, 9]]);
+        network.update(0x0123456789abcdef, 100., 0.1);
+        assert!((network.value(0x0123456789abcdef) - 10.).abs() < 1e-4);
+    }
+
+    #[test]
+    fn it_learns_from_self_play() {
+        GameEngine::new();
+        let mut network = NTupleNetwork::default();
+        let mut rng = StdRng::seed_from_u64(1);
+        let board = network.train_game(0.01, &mut rng);
+        assert!(GameEngine::is_game_over(board));
+        assert!(network.weights.iter().flatten().any(|&weight| weight != 0.));
+        assert_eq!(network.games_trained(), 1);
+    }
+
+    #[test]
+    fn it_evaluates_expectimax_leaves() {
+        use crate::expectimax::Expectimax;
+        use std::sync::Arc;
+
+        GameEngine::new();
+        let mut expectimax = Expectimax::with_evaluator(Arc::new(NTupleNetwork::default())).with_max_depth(1);
+        assert!(expectimax.get_next_move(0x1100000000000000).is_some());
+        assert!(expectimax.get_next_move(0x1234432112344321).is_none());
+    }
+
+    #[test]
+    fn it_saves_and_loads_weights() {
+        GameEngine::new();
+        let mut network = NTupleNetwork::new(vec![vec![0, 1, 2], vec![3, 7, 11, 15]]);
+        network.update(0x1200340000000005, 10., 0.5);
+        network.games_trained = 7;
+        let path = std::env::temp_dir().join(format!("ai_2048_ntuple_round_trip_{}.bin", std::process::id()));
+        network.save(&path).unwrap();
+        let loaded = NTupleNetwork::load(&path).unwrap();
+        assert!(!path.with_extension("bin.tmp").exists());
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.shapes(), network.shapes());
+        assert_eq!(loaded.value(0x1200340000000005), network.value(0x1200340000000005));
+        assert_eq!(loaded.games_trained(), 7);
+    }
+}
@@ -1,12 +1,23 @@
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, SecondsFormat, Utc};
+use rand::Rng;
 use serde::{Deserialize, Serialize};
+use std::fmt;
 use std::fs::OpenOptions;
-use std::io::Write;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime};
+use crate::data_dir::DataDir;
+use crate::difficulty::{self, Difficulty};
 use crate::engine::{Board, Move};
+use crate::move_quality::{MoveGrade, MoveQualityAnalyzer};
+use crate::schema::{self, GameMove, Source, SCHEMA_VERSION};
+use crate::store::{FileStore, SessionStore};
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct MoveData {
-    pub timestamp: DateTime<Utc>,
+/// A row of a moves file written before the canonical schema, read leniently so that files from
+/// every older version load.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MoveRecord {
+    pub timestamp: String,
     pub board_state: String,
     pub move_chosen: String,
     pub time_taken_ms: u64,
@@ -17,14 +28,110 @@ pub struct MoveData {
     pub best_score: f64,
     pub chosen_score: f64,
     pub variation_score: f64,
+    // Missing from recordings made before difficulty ratings
+    #[serde(default)]
+    pub difficulty: Option<Difficulty>,
+    // Missing from recordings made before move grades
+    #[serde(default)]
+    pub move_grade: Option<MoveGrade>,
     pub is_bad_move: bool,
     pub game_score: u64,
     pub move_number: u32,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+impl MoveRecord {
+    /// The board before the move, `None` if the recorded state is malformed.
+    pub fn board(&self) -> Option<Board> {
+        parse_board(&self.board_state)
+    }
+
+    pub fn chosen_move(&self) -> Option<Move> {
+        parse_move(&self.move_chosen)
+    }
+}
+
+/// How a game is timed, named as in the web frontend.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GameMode {
+    #[default]
+    Normal,
+    CountUp,
+    CountDown,
+}
+
+impl fmt::Display for GameMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            GameMode::Normal => "normal",
+            GameMode::CountUp => "countup",
+            GameMode::CountDown => "countdown",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for GameMode {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_lowercase().as_str() {
+            "normal" | "none" => Ok(GameMode::Normal),
+            "countup" => Ok(GameMode::CountUp),
+            "countdown" => Ok(GameMode::CountDown),
+            _ => Err(format!("Unknown game mode: {} (normal, countup or countdown)", name)),
+        }
+    }
+}
+
+/// How much of the AI's analysis the player sees, so that unassisted decisions can be measured.
+/// Moves are scored and recorded the same way in every mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CoachingMode {
+    /// The AI panel is shown before every move and the move's quality after it
+    #[default]
+    Full,
+    /// Nothing is shown
+    Blind,
+    /// The AI panel is shown only when asked for
+    Hints,
+    /// The AI panel and the move's quality are shown once the move is made
+    Feedback,
+}
+
+impl fmt::Display for CoachingMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            CoachingMode::Full => "full",
+            CoachingMode::Blind => "blind",
+            CoachingMode::Hints => "hints",
+            CoachingMode::Feedback => "feedback",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for CoachingMode {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_lowercase().as_str() {
+            "full" => Ok(CoachingMode::Full),
+            "blind" => Ok(CoachingMode::Blind),
+            "hints" | "hint" => Ok(CoachingMode::Hints),
+            "feedback" => Ok(CoachingMode::Feedback),
+            _ => Err(format!("Unknown coaching mode: {} (full, blind, hints or feedback)", name)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GameSession {
     pub session_id: String,
+    // Missing from sessions recorded before players and modes
+    pub player_name: Option<String>,
+    pub game_mode: Option<GameMode>,
     pub start_time: DateTime<Utc>,
     pub end_time: Option<DateTime<Utc>>,
     pub final_score: u64,
@@ -32,91 +139,277 @@ pub struct GameSession {
     pub total_moves: u32,
     pub bad_moves: u32,
     pub average_time_per_move_ms: f64,
+    /// Seconds from the start of the game to its end, missing from older sessions
+    pub game_time: Option<u64>,
+    // Missing from sessions recorded before coaching modes
+    #[serde(default)]
+    pub coaching: Option<CoachingMode>,
+    #[serde(default)]
+    pub hints_requested: Option<u32>,
+    /// Tile exponents of the first position, row by row; missing from sessions recorded before
+    /// games could start from a given board
+    #[serde(default)]
+    pub starting_board: Option<String>,
+    /// Seed the tile spawns were drawn from, empty for unseeded games
+    #[serde(default)]
+    pub seed: Option<u64>,
+    /// False for a game that was abandoned and recovered from its moves file, missing from
+    /// sessions recorded before moves were logged as they happen
+    #[serde(default)]
+    pub complete: Option<bool>,
+}
+
+/// A row of the CLI's session summary, as the analysis tool reads it. The web server's summary
+/// and the session in a `game_session_*.json` file read into it too, through the names of their
+/// columns and keys, leaving out what only the CLI records.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionRecord {
+    #[serde(alias = "Session ID", alias = "sessionId")]
+    pub session_id: String,
+    // Missing from files written before players and modes
+    #[serde(default, alias = "Player Name", alias = "playerName")]
+    pub player_name: Option<String>,
+    #[serde(default, alias = "Game Mode", alias = "gameMode")]
+    pub game_mode: Option<String>,
+    #[serde(alias = "Start Time", alias = "startTime")]
+    pub start_time: String,
+    #[serde(default, alias = "End Time", alias = "endTime")]
+    pub end_time: Option<String>,
+    #[serde(alias = "Final Score", alias = "finalScore")]
+    pub final_score: u64,
+    #[serde(alias = "Highest Tile", alias = "highestTile")]
+    pub highest_tile: u64,
+    #[serde(alias = "Total Moves", alias = "totalMoves")]
+    pub total_moves: u32,
+    // Only the CLI grades moves and times them in its summary
+    #[serde(default)]
+    pub bad_moves: Option<u32>,
+    #[serde(default)]
+    pub average_time_per_move_ms: Option<f64>,
+    #[serde(default, alias = "Game Time", alias = "gameTime")]
+    pub game_time: Option<u64>,
+    // Missing from files written before coaching modes, when the AI panel was always shown
+    #[serde(default)]
+    pub coaching: Option<String>,
+    #[serde(default)]
+    pub hints_requested: Option<u32>,
+    #[serde(default)]
+    pub starting_board: Option<String>,
+    #[serde(default)]
+    pub seed: Option<u64>,
+    #[serde(default)]
+    pub complete: Option<bool>,
+}
+
+impl From<&GameSession> for SessionRecord {
+    fn from(session: &GameSession) -> Self {
+        let format_time = |time: DateTime<Utc>| time.format("%Y-%m-%d %H:%M:%S").to_string();
+        SessionRecord {
+            session_id: session.session_id.clone(),
+            player_name: session.player_name.clone(),
+            game_mode: session.game_mode.map(|mode| mode.to_string()),
+            start_time: format_time(session.start_time),
+            end_time: session.end_time.map(format_time),
+            final_score: session.final_score,
+            highest_tile: session.highest_tile,
+            total_moves: session.total_moves,
+            bad_moves: Some(session.bad_moves),
+            average_time_per_move_ms: Some((session.average_time_per_move_ms * 100.0).round() / 100.0),
+            game_time: session.game_time,
+            coaching: session.coaching.map(|coaching| coaching.to_string()),
+            hints_requested: session.hints_requested,
+            starting_board: session.starting_board.clone(),
+            seed: session.seed,
+            complete: session.complete,
+        }
+    }
+}
+
+/// Reads a session summary written by the CLI, of any version, or by the web server.
+pub fn read_session_records<P: AsRef<Path>>(filename: P) -> Result<Vec<SessionRecord>, Box<dyn std::error::Error>> {
+    let mut reader = csv::Reader::from_path(&filename)?;
+    let mut records = Vec::new();
+    for result in reader.deserialize() {
+        let record: SessionRecord = result.map_err(|e| format!("{}: {}", filename.as_ref().display(), e))?;
+        records.push(record);
+    }
+    Ok(records)
 }
 
 pub struct PlayerDataRecorder {
     session_id: String,
-    moves_data: Vec<MoveData>,
+    player_name: Option<String>,
+    // The agent making the moves in autoplay
+    agent: Option<String>,
+    game_mode: GameMode,
+    coaching: CoachingMode,
+    // Whether the player asked for a hint before the next recorded move
+    hint_requested: bool,
+    starting_board: Option<Board>,
+    seed: Option<u64>,
+    moves_data: Vec<GameMove>,
+    store: Box<dyn SessionStore>,
     session_start: DateTime<Utc>,
+    // Grades each move by what it gives up in rollouts; `None` leaves moves ungraded
+    move_quality: Option<MoveQualityAnalyzer>,
+}
+
+impl Default for PlayerDataRecorder {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl PlayerDataRecorder {
     pub fn new() -> Self {
-        let session_id = Utc::now().format("%Y%m%d_%H%M%S").to_string();
         Self {
-            session_id,
+            session_id: new_session_id(),
+            player_name: None,
+            agent: None,
+            game_mode: GameMode::Normal,
+            coaching: CoachingMode::Full,
+            hint_requested: false,
+            starting_board: None,
+            seed: None,
             moves_data: Vec::new(),
+            store: Box::new(FileStore::new(DataDir::default())),
             session_start: Utc::now(),
+            move_quality: Some(MoveQualityAnalyzer::new(200)),
         }
     }
 
+    /// Names the player in every output; blank names are left out.
+    pub fn with_player_name(mut self, player_name: &str) -> Self {
+        let player_name = player_name.trim();
+        self.player_name = if player_name.is_empty() {
+            None
+        } else {
+            Some(player_name.to_string())
+        };
+        self
+    }
+
+    pub fn with_game_mode(mut self, game_mode: GameMode) -> Self {
+        self.game_mode = game_mode;
+        self
+    }
+
+    pub fn with_coaching(mut self, coaching: CoachingMode) -> Self {
+        self.coaching = coaching;
+        self
+    }
+
+    /// Writes the recordings into `data_dir` rather than the current directory.
+    pub fn with_data_dir(self, data_dir: DataDir) -> Self {
+        self.with_store(FileStore::new(data_dir))
+    }
+
+    /// Records into `store` rather than files in the current directory.
+    pub fn with_store<S: SessionStore + 'static>(mut self, store: S) -> Self {
+        self.store = Box::new(store);
+        self
+    }
+
+    /// Records the position the game starts from and the seed its tiles are drawn from, if any.
+    pub fn with_start(mut self, board: Board, seed: Option<u64>) -> Self {
+        self.starting_board = Some(board);
+        self.seed = seed;
+        self
+    }
+
+    /// Replaces the analyzer that grades each recorded move.
+    pub fn with_move_quality(mut self, move_quality: MoveQualityAnalyzer) -> Self {
+        self.move_quality = Some(move_quality);
+        self
+    }
+
+    /// Records moves without grading them, leaving their grade and losses empty.
+    pub fn without_move_quality(mut self) -> Self {
+        self.move_quality = None;
+        self
+    }
+
+    /// Tells the store the game has started. Files mark the session incomplete until it is
+    /// saved, so that a game cut short can be recovered with `recover_sessions`.
+    pub fn begin(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let session = self.session_summary(0, 0);
+        self.store.begin_session(&session)
+    }
+
+    /// Records the following moves as played by the named agent, or by the player again with
+    /// `None`.
+    pub fn set_agent(&mut self, agent: Option<&str>) {
+        self.agent = agent.map(String::from);
+    }
+
+    /// Logs a hint request against the next recorded move.
+    pub fn request_hint(&mut self) {
+        self.hint_requested = true;
+    }
+
     pub fn record_move(
         &mut self,
         board: Board,
         move_chosen: Move,
         time_taken_ms: u64,
-        move_scores: [f64; 4], // [Up, Down, Left, Right]
+        move_scores: [Option<f64>; 4], // [Up, Down, Left, Right], None for invalid moves
         game_score: u64,
         move_number: u32,
-    ) {
-        let [up_score, down_score, left_score, right_score] = move_scores;
-        
-        // Find best score
-        let best_score = move_scores.iter().fold(0.0/0.0, |m, v| v.max(m));
-        
-        // Get chosen score
-        let chosen_score = match move_chosen {
-            Move::Up => up_score,
-            Move::Down => down_score,
-            Move::Left => left_score,
-            Move::Right => right_score,
-        };
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let rating = difficulty::rate_position(board, &move_scores);
 
-        // Calculate variation score (standard deviation)
-        let mean_score = move_scores.iter().sum::<f64>() / 4.0;
-        let variance = move_scores.iter()
-            .map(|score| (score - mean_score).powi(2))
-            .sum::<f64>() / 4.0;
-        let variation_score = variance.sqrt();
-
-        // Detect bad move (chosen score is significantly worse than best)
-        let score_threshold = 0.1; // 10% threshold for bad move detection
-        let is_bad_move = if best_score > 0.0 {
-            (best_score - chosen_score) / best_score > score_threshold
-        } else {
-            false
-        };
+        // Grade the move by what it gives up in rollouts; mistakes and blunders are bad moves
+        let quality = self
+            .move_quality
+            .as_mut()
+            .and_then(|move_quality| move_quality.assess(board, move_chosen));
+        let move_grade = quality.map(|quality| quality.grade);
+        let is_bad_move = move_grade.is_some_and(|grade| grade >= MoveGrade::Mistake);
 
-        let move_data = MoveData {
-            timestamp: Utc::now(),
+        let move_data = GameMove {
+            schema_version: SCHEMA_VERSION,
+            source: Source::Cli,
+            session_id: self.session_id.clone(),
+            player_name: self.player_name.clone(),
+            game_mode: Some(self.game_mode.to_string()),
+            move_number,
+            timestamp: Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
             board_state: board_to_string(board),
-            move_chosen: move_to_string(move_chosen),
+            direction: move_chosen,
             time_taken_ms,
-            up_score,
-            down_score,
-            left_score,
-            right_score,
-            best_score,
-            chosen_score,
-            variation_score,
-            is_bad_move,
             game_score,
-            move_number,
+            up_score: move_scores[0],
+            down_score: move_scores[1],
+            left_score: move_scores[2],
+            right_score: move_scores[3],
+            is_bad_move,
+            difficulty: Some(rating.level),
+            move_grade,
+            expected_score_loss: quality.map(|quality| quality.expected_score_loss),
+            win_probability_loss: quality.map(|quality| quality.win_probability_loss),
+            tiles_added: None,
+            tiles_removed: None,
+            coaching: Some(self.coaching),
+            hint_requested: Some(std::mem::take(&mut self.hint_requested)),
+            agent: self.agent.clone(),
         };
 
+        self.store.append_move(&move_data)?;
         self.moves_data.push(move_data);
+        Ok(())
     }
 
-    pub fn save_session_data(&self, final_score: u64, highest_tile: u64) -> Result<(), Box<dyn std::error::Error>> {
-        // Save moves data to CSV
-        let moves_filename = format!("player_moves_{}.csv", self.session_id);
-        let mut moves_writer = csv::Writer::from_path(&moves_filename)?;
-
-        for move_data in &self.moves_data {
-            moves_writer.serialize(move_data)?;
-        }
-        moves_writer.flush()?;
+    pub fn save_session_data(&mut self, final_score: u64, highest_tile: u64) -> Result<(), Box<dyn std::error::Error>> {
+        let mut session = self.session_summary(final_score, highest_tile);
+        let end_time = Utc::now();
+        session.end_time = Some(end_time);
+        session.game_time = Some((end_time - self.session_start).num_seconds().max(0) as u64);
+        session.complete = Some(true);
+        self.store.finish_session(&session)
+    }
 
+    // The session as recorded so far, without an end
+    fn session_summary(&self, final_score: u64, highest_tile: u64) -> GameSession {
         // Calculate session statistics
         let total_moves = self.moves_data.len() as u32;
         let bad_moves = self.moves_data.iter().filter(|m| m.is_bad_move).count() as u32;
@@ -127,63 +420,229 @@ impl PlayerDataRecorder {
             0.0
         };
 
-        let session = GameSession {
+        GameSession {
             session_id: self.session_id.clone(),
+            player_name: self.player_name.clone(),
+            game_mode: Some(self.game_mode),
             start_time: self.session_start,
-            end_time: Some(Utc::now()),
+            end_time: None,
             final_score,
             highest_tile,
             total_moves,
             bad_moves,
             average_time_per_move_ms,
-        };
-
-        // Save session data
-        let session_filename = format!("player_sessions_{}.json", 
-            self.session_start.format("%Y%m"));
-        
-        let mut sessions = load_existing_sessions(&session_filename)?;
-        sessions.push(session);
-        
-        let json_data = serde_json::to_string_pretty(&sessions)?;
-        std::fs::write(&session_filename, json_data)?;
-
-        // Also append to overall CSV for easy analysis
-        let csv_filename = "player_sessions.csv";
-        let file_exists = std::path::Path::new(csv_filename).exists();
-        
-        let mut file = OpenOptions::new()
-            .create(true)
-            .append(true)
-            .open(csv_filename)?;
-
-        if !file_exists {
-            writeln!(file, "session_id,start_time,end_time,final_score,highest_tile,total_moves,bad_moves,average_time_per_move_ms")?;
+            game_time: None,
+            coaching: Some(self.coaching),
+            hints_requested: Some(self.moves_data.iter().filter(|m| m.hint_requested == Some(true)).count() as u32),
+            starting_board: self.starting_board.map(board_to_string),
+            seed: self.seed,
+            complete: None,
         }
+    }
 
-        let session = &sessions[sessions.len() - 1];
-        writeln!(file, "{},{},{:?},{},{},{},{},{:.2}",
-            session.session_id,
-            session.start_time.format("%Y-%m-%d %H:%M:%S"),
-            session.end_time.map(|t| t.format("%Y-%m-%d %H:%M:%S").to_string()).unwrap_or_default(),
-            session.final_score,
-            session.highest_tile,
-            session.total_moves,
-            session.bad_moves,
-            session.average_time_per_move_ms
-        )?;
-
-        println!("Data saved to {} and {}", moves_filename, csv_filename);
-        Ok(())
+    /// The most recently recorded move.
+    pub fn last_move(&self) -> Option<&GameMove> {
+        self.moves_data.last()
     }
 
     pub fn get_session_id(&self) -> &str {
         &self.session_id
     }
+
+    pub fn store(&self) -> &dyn SessionStore {
+        self.store.as_ref()
+    }
+}
+
+const SESSIONS_CSV_HEADER: [&str; 16] = [
+    "session_id",
+    "player_name",
+    "game_mode",
+    "start_time",
+    "end_time",
+    "final_score",
+    "highest_tile",
+    "total_moves",
+    "bad_moves",
+    "average_time_per_move_ms",
+    "game_time",
+    "coaching",
+    "hints_requested",
+    "starting_board",
+    "seed",
+    "complete",
+];
+
+/// Reads the sessions of a monthly `player_sessions_<YYYYMM>.json` file.
+pub fn read_sessions_json<P: AsRef<Path>>(filename: P) -> Result<Vec<SessionRecord>, Box<dyn std::error::Error>> {
+    let sessions = load_existing_sessions(filename.as_ref())
+        .map_err(|e| format!("{}: {}", filename.as_ref().display(), e))?;
+    Ok(sessions.iter().map(SessionRecord::from).collect())
+}
+
+/// Adds a finished session to the monthly JSON file and to the CLI's session summary.
+pub(crate) fn append_session(data_dir: &DataDir, session: &GameSession) -> Result<(), Box<dyn std::error::Error>> {
+    let session_filename = data_dir.cli_sessions_json(session.start_time);
+    let mut sessions = load_existing_sessions(&session_filename)?;
+    sessions.push(session.clone());
+    let json_data = serde_json::to_string_pretty(&sessions)?;
+    std::fs::write(&session_filename, json_data)?;
+
+    // Also append to overall CSV for easy analysis
+    let csv_filename = data_dir.cli_summary();
+    let file_exists = csv_filename.exists();
+    if file_exists {
+        upgrade_csv_header(&csv_filename, &SESSIONS_CSV_HEADER)?;
+    }
+
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&csv_filename)?;
+    let mut writer = csv::WriterBuilder::new().has_headers(!file_exists).from_writer(file);
+    writer.serialize(SessionRecord::from(session))?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// How long an unfinished game must go without a move before `recover_sessions` takes it for
+/// abandoned rather than still being played by another CLI sharing the data directory.
+pub const ABANDONED_AFTER: Duration = Duration::from_secs(60 * 60);
+
+/// Saves the sessions of games that were started but never finished, such as after a crash,
+/// from the moves logged before they stopped. The board after the last logged move stands in
+/// for the final board, and the session is marked incomplete. Games with a move in the last
+/// `ABANDONED_AFTER` are left alone.
+pub fn recover_sessions(data_dir: &DataDir) -> Result<Vec<GameSession>, Box<dyn std::error::Error>> {
+    recover_sessions_idle_for(data_dir, ABANDONED_AFTER)
+}
+
+/// `recover_sessions` for games whose marker and moves file have not changed for `idle`.
+pub fn recover_sessions_idle_for(
+    data_dir: &DataDir,
+    idle: Duration,
+) -> Result<Vec<GameSession>, Box<dyn std::error::Error>> {
+    let mut recovered = Vec::new();
+    for marker in data_dir.incomplete_markers()? {
+        let mut session: GameSession = serde_json::from_str(&std::fs::read_to_string(&marker)?)?;
+        // Saved already if the game stopped between saving its session and removing the marker
+        if load_existing_sessions(&data_dir.cli_sessions_json(session.start_time))?
+            .iter()
+            .any(|saved| saved.session_id == session.session_id)
+        {
+            std::fs::remove_file(&marker)?;
+            continue;
+        }
+        // Every move is flushed to the moves file, so a game being played keeps it fresh
+        let moves_file = data_dir.cli_moves(&session.session_id);
+        let last_change = [&marker, &moves_file]
+            .iter()
+            .filter_map(|path| std::fs::metadata(path).and_then(|meta| meta.modified()).ok())
+            .max();
+        let idle_for = last_change.map(|time| SystemTime::now().duration_since(time).unwrap_or_default());
+        if idle_for.is_some_and(|idle_for| idle_for < idle) {
+            continue;
+        }
+
+        // A move cut off part way through writing ends the log, and is dropped from the file so
+        // that it reads back like any other
+        let moves: Vec<GameMove> = match csv::Reader::from_path(&moves_file) {
+            Ok(mut reader) => reader.deserialize().map_while(Result::ok).collect(),
+            Err(_) => Vec::new(),
+        };
+        if moves_file.exists() {
+            replace_file(&moves_file, |tmp| schema::write_moves(&tmp.to_string_lossy(), &moves))?;
+        }
+        let last_board = moves
+            .last()
+            .and_then(|mv| mv.board().map(|board| crate::engine::shift(board, mv.direction)));
+        if let Some(board) = last_board {
+            session.final_score = crate::engine::get_score(board);
+            session.highest_tile = crate::engine::get_highest_tile_val(board);
+        }
+        session.total_moves = moves.len() as u32;
+        session.bad_moves = moves.iter().filter(|m| m.is_bad_move).count() as u32;
+        session.average_time_per_move_ms =
+            moves.iter().map(|m| m.time_taken_ms).sum::<u64>() as f64 / moves.len().max(1) as f64;
+        session.hints_requested = Some(moves.iter().filter(|m| m.hint_requested == Some(true)).count() as u32);
+        let end_time = moves
+            .last()
+            .and_then(|mv| DateTime::parse_from_rfc3339(&mv.timestamp).ok())
+            .map_or(session.start_time, |time| time.with_timezone(&Utc));
+        session.end_time = Some(end_time);
+        session.game_time = Some((end_time - session.start_time).num_seconds().max(0) as u64);
+        session.complete = Some(false);
+
+        append_session(data_dir, &session)?;
+        std::fs::remove_file(&marker)?;
+        recovered.push(session);
+    }
+    Ok(recovered)
+}
+
+/// A session ID like the web frontend's: the start time plus a random suffix, so that games
+/// started in the same second still get their own files.
+pub fn new_session_id() -> String {
+    const CHARS: &[u8] = b"0123456789abcdefghijklmnopqrstuvwxyz";
+    let mut rng = rand::thread_rng();
+    let suffix: String = (0..5)
+        .map(|_| CHARS[rng.gen_range(0, CHARS.len())] as char)
+        .collect();
+    format!("{}_{}", Utc::now().format("%Y%m%d_%H%M%S"), suffix)
 }
 
-fn load_existing_sessions(filename: &str) -> Result<Vec<GameSession>, Box<dyn std::error::Error>> {
-    if std::path::Path::new(filename).exists() {
+// Rewrites a CSV file written with older columns under `header`, leaving new columns empty
+fn upgrade_csv_header<P: AsRef<Path>>(filename: P, header: &[&str]) -> Result<(), Box<dyn std::error::Error>> {
+    let mut reader = csv::Reader::from_path(&filename)?;
+    let old_header = reader.headers()?.clone();
+    if old_header.iter().eq(header.iter().copied()) {
+        return Ok(());
+    }
+    let rows = reader.records().collect::<Result<Vec<_>, _>>()?;
+
+    replace_file(filename.as_ref(), |tmp| {
+        let mut writer = csv::Writer::from_path(tmp)?;
+        writer.write_record(header)?;
+        for row in &rows {
+            writer.write_record(header.iter().map(|&column| {
+                old_header
+                    .iter()
+                    .position(|old_column| old_column == column)
+                    .and_then(|idx| row.get(idx))
+                    .unwrap_or("")
+            }))?;
+        }
+        writer.flush()?;
+        Ok(())
+    })
+}
+
+// Writes the new contents of `filename` to a temporary file and renames it over the old, so that
+// a crash part way through never leaves the file half written
+fn replace_file<F>(filename: &Path, write: F) -> Result<(), Box<dyn std::error::Error>>
+where
+    F: FnOnce(&Path) -> Result<(), Box<dyn std::error::Error>>,
+{
+    let mut tmp = filename.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = Path::new(&tmp);
+    write(tmp)?;
+    std::fs::rename(tmp, filename)?;
+    Ok(())
+}
+
+/// Reads a `player_moves_<session>.csv` file.
+pub fn load_moves(filename: &str) -> Result<Vec<MoveRecord>, Box<dyn std::error::Error>> {
+    let mut reader = csv::Reader::from_path(filename)?;
+    let mut moves = Vec::new();
+    for result in reader.deserialize() {
+        moves.push(result?);
+    }
+    Ok(moves)
+}
+
+fn load_existing_sessions(filename: &Path) -> Result<Vec<GameSession>, Box<dyn std::error::Error>> {
+    if filename.exists() {
         let content = std::fs::read_to_string(filename)?;
         let sessions: Vec<GameSession> = serde_json::from_str(&content)?;
         Ok(sessions)
@@ -192,16 +651,177 @@ fn load_existing_sessions(filename: &str) -> Result<Vec<GameSession>, Box<dyn st
     }
 }
 
-fn board_to_string(board: Board) -> String {
+/// Writes a board as 16 comma separated tile exponents, row by row.
+pub fn board_to_string(board: Board) -> String {
     let board_vec = crate::engine::to_vec(board);
     board_vec.iter().map(|&x| x.to_string()).collect::<Vec<_>>().join(",")
 }
 
-fn move_to_string(move_dir: Move) -> String {
-    match move_dir {
-        Move::Up => "Up".to_string(),
-        Move::Down => "Down".to_string(),
-        Move::Left => "Left".to_string(),
-        Move::Right => "Right".to_string(),
+/// Parses the 16 comma separated tile exponents written for `board_state`.
+pub fn parse_board(board_state: &str) -> Option<Board> {
+    let tiles: Vec<u8> = board_state
+        .split(',')
+        .map(|tile| tile.trim().parse().ok().filter(|&tile| tile <= crate::engine::MAX_TILE_EXPONENT))
+        .collect::<Option<_>>()?;
+    if tiles.len() == 16 {
+        Some(crate::engine::from_vec(&tiles))
+    } else {
+        None
+    }
+}
+
+pub fn parse_move(move_chosen: &str) -> Option<Move> {
+    match move_chosen {
+        "Up" => Some(Move::Up),
+        "Down" => Some(Move::Down),
+        "Left" => Some(Move::Left),
+        "Right" => Some(Move::Right),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::MemoryStore;
+
+    #[test]
+    fn it_makes_distinct_session_ids() {
+        let first = new_session_id();
+        let second = new_session_id();
+        assert_ne!(first, second);
+        // 20250801_162156_oej58
+        assert_eq!(first.len(), 21);
+        assert_eq!(first.matches('_').count(), 2);
+    }
+
+    #[test]
+    fn it_parses_game_modes() {
+        assert_eq!("countdown".parse(), Ok(GameMode::CountDown));
+        assert_eq!("none".parse(), Ok(GameMode::Normal));
+        assert!("sideways".parse::<GameMode>().is_err());
+        assert_eq!(GameMode::CountUp.to_string(), "countup");
+        assert_eq!("Hints".parse(), Ok(CoachingMode::Hints));
+        assert!("loud".parse::<CoachingMode>().is_err());
+    }
+
+    #[test]
+    fn it_credits_autoplay_moves_to_the_agent() {
+        crate::engine::new();
+        let mut recorder = PlayerDataRecorder::new()
+            .with_player_name("Ann")
+            .with_store(MemoryStore::new())
+            .with_move_quality(MoveQualityAnalyzer::new(2));
+        let board = 0x1100_0000_0000_0000;
+        recorder.set_agent(Some("expectimax"));
+        recorder.record_move(board, Move::Left, 5, [None, Some(4.), Some(4.), Some(4.)], 0, 1).unwrap();
+        let first = recorder.last_move().unwrap();
+        assert_eq!((first.player_name.as_deref(), first.agent.as_deref()), (Some("Ann"), Some("expectimax")));
+        recorder.set_agent(None);
+        recorder.request_hint();
+        recorder.record_move(board, Move::Right, 5, [None, Some(4.), Some(4.), Some(4.)], 0, 2).unwrap();
+        let last = recorder.last_move().unwrap();
+        assert_eq!((last.player_name.as_deref(), last.agent.as_deref()), (Some("Ann"), None));
+        assert_eq!(last.hint_requested, Some(true));
+        assert_eq!(recorder.moves_data[0].hint_requested, Some(false));
+        assert!(last.move_grade.is_some() && last.expected_score_loss.is_some());
+
+        // Moves recorded without rollouts go ungraded
+        let mut recorder = PlayerDataRecorder::new().with_store(MemoryStore::new()).without_move_quality();
+        recorder.record_move(board, Move::Left, 5, [None, Some(4.), Some(4.), Some(4.)], 0, 1).unwrap();
+        let last = recorder.last_move().unwrap();
+        assert_eq!((last.move_grade, last.is_bad_move, last.win_probability_loss), (None, false, None));
+    }
+
+    #[test]
+    fn it_reads_back_the_sessions_it_writes() {
+        crate::engine::new();
+        let root = std::env::temp_dir().join(format!("ai_2048_session_round_trip_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        let data_dir = DataDir::new(&root);
+        let mut recorder = PlayerDataRecorder::new()
+            .with_player_name("Smith, \"J\"")
+            .with_game_mode(GameMode::CountDown)
+            .with_coaching(CoachingMode::Hints)
+            .with_start(0x1100_0000_0000_0000, Some(42))
+            .with_data_dir(data_dir.clone())
+            .with_move_quality(MoveQualityAnalyzer::new(2));
+        recorder.begin().unwrap();
+        recorder.record_move(0x1100_0000_0000_0000, Move::Left, 40, [None, Some(4.), Some(4.), Some(4.)], 0, 1).unwrap();
+        recorder.save_session_data(4, 4).unwrap();
+
+        let header = std::fs::read_to_string(data_dir.cli_summary()).unwrap();
+        assert_eq!(header.lines().next(), Some(SESSIONS_CSV_HEADER.join(",").as_str()));
+        let records = read_session_records(data_dir.cli_summary()).unwrap();
+        let logged = schema::read_moves(&data_dir.cli_moves(recorder.get_session_id()).to_string_lossy()).unwrap();
+        std::fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(records.len(), 1);
+        let record = &records[0];
+        assert_eq!(record.session_id, recorder.get_session_id());
+        assert_eq!(record.player_name.as_deref(), Some("Smith, \"J\""));
+        assert_eq!(record.game_mode.as_deref(), Some("countdown"));
+        assert_eq!(record.end_time.as_ref().map(String::len), Some(19));
+        assert_eq!((record.final_score, record.total_moves, record.average_time_per_move_ms), (4, 1, Some(40.0)));
+        assert_eq!(record.coaching.as_deref(), Some("hints"));
+        assert_eq!(record.starting_board.as_deref(), Some("1,1,0,0,0,0,0,0,0,0,0,0,0,0,0,0"));
+        assert_eq!((record.seed, record.complete), (Some(42), Some(true)));
+        assert_eq!(logged, recorder.moves_data);
+
+        // The web server's summary reads into the same records
+        let web = read_session_records("deploy/data/player_sessions.csv").unwrap();
+        assert_eq!(web.len(), 3);
+        assert_eq!(web[0].player_name.as_deref(), Some("Simon"));
+        assert_eq!(web[0].bad_moves, None);
+    }
+
+    #[test]
+    fn it_recovers_abandoned_sessions() {
+        crate::engine::new();
+        let root = std::env::temp_dir().join(format!("ai_2048_recover_sessions_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        let data_dir = DataDir::new(&root);
+        let mut recorder = PlayerDataRecorder::new()
+            .with_player_name("Ann")
+            .with_data_dir(data_dir.clone());
+        recorder.begin().unwrap();
+        recorder.record_move(0x1100_0000_0000_0000, Move::Left, 40, [None, Some(4.), Some(4.), Some(4.)], 0, 1).unwrap();
+        recorder.record_move(0x2000_1000_0000_0000, Move::Right, 60, [Some(4.), None, Some(4.), Some(4.)], 4, 2).unwrap();
+        // The game stops part way through writing its third move
+        let mut log = OpenOptions::new().append(true).open(data_dir.cli_moves(recorder.get_session_id())).unwrap();
+        std::io::Write::write_all(&mut log, b"1,cli,").unwrap();
+        drop(log);
+
+        // A game that just moved may still be going on in another terminal
+        assert!(recover_sessions(&data_dir).unwrap().is_empty());
+        let recovered = recover_sessions_idle_for(&data_dir, Duration::from_secs(0)).unwrap();
+        let markers = data_dir.incomplete_markers().unwrap();
+        let records = read_session_records(data_dir.cli_summary()).unwrap();
+        let logged = schema::read_moves(&data_dir.cli_moves(recorder.get_session_id()).to_string_lossy()).unwrap();
+        std::fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(recovered.len(), 1);
+        let session = &recovered[0];
+        assert_eq!(session.session_id, recorder.get_session_id());
+        assert_eq!(session.player_name.as_deref(), Some("Ann"));
+        assert_eq!((session.total_moves, session.complete), (2, Some(false)));
+        let final_board = crate::engine::shift(0x2000_1000_0000_0000, Move::Right);
+        assert_eq!(session.final_score, crate::engine::get_score(final_board));
+        assert_eq!(session.average_time_per_move_ms, 50.0);
+        assert!(markers.is_empty());
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].complete, Some(false));
+        assert_eq!(logged, recorder.moves_data);
+    }
+
+    #[test]
+    fn it_upgrades_old_session_files() {
+        let filename = std::env::temp_dir().join(format!("ai_2048_upgrade_sessions_{}.csv", std::process::id()));
+        let filename = filename.to_str().unwrap();
+        std::fs::write(filename, "session_id,final_score\n20250731_233122,124\n").unwrap();
+        upgrade_csv_header(filename, &["session_id", "player_name", "final_score"]).unwrap();
+        let content = std::fs::read_to_string(filename).unwrap();
+        std::fs::remove_file(filename).unwrap();
+        assert_eq!(content, "session_id,player_name,final_score\n20250731_233122,,124\n");
     }
 }
@@ -0,0 +1,239 @@
+use crate::agent::{best_move, Agent};
+use crate::engine::{Board, Move, MOVES};
+use crate::player_data;
+use crate::schema::GameMove;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::error::Error;
+use std::io::{BufRead, BufReader};
+
+/// A position with one clearly best move, scored by the AI when it was made.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Puzzle {
+    pub puzzle_id: String,
+    /// Tile exponents, row by row
+    pub board_state: String,
+    /// Where the position was found, empty for hand-made puzzles
+    pub session_id: Option<String>,
+    pub move_number: Option<u32>,
+    /// The move the player made in the recorded game
+    pub played: Option<Move>,
+    pub best_move: Move,
+    /// The agent whose scores rank the moves
+    pub agent: String,
+    /// Scores in `[Up, Down, Left, Right]` order, empty for invalid moves
+    pub up_score: Option<f64>,
+    pub down_score: Option<f64>,
+    pub left_score: Option<f64>,
+    pub right_score: Option<f64>,
+    /// How far the runner-up falls short of the best move, relative to the best
+    pub score_gap: f64,
+}
+
+impl Puzzle {
+    pub fn board(&self) -> Option<Board> {
+        player_data::parse_board(&self.board_state)
+    }
+
+    pub fn scores(&self) -> [Option<f64>; 4] {
+        [self.up_score, self.down_score, self.left_score, self.right_score]
+    }
+
+    /// Grades an answer against the AI's ranking of the moves.
+    pub fn grade(&self, answer: Move) -> PuzzleResult {
+        let scores = self.scores();
+        let best = scores.iter().flatten().copied().fold(f64::NEG_INFINITY, f64::max);
+        let answer_score = scores[MOVES.iter().position(|&m| m == answer).unwrap()];
+        match answer_score {
+            Some(score) => PuzzleResult {
+                solved: score >= best,
+                rank: Some(1 + scores.iter().flatten().filter(|&&other| other > score).count()),
+                loss: (best - score) / best.abs().max(f64::EPSILON),
+            },
+            None => PuzzleResult {
+                solved: false,
+                rank: None,
+                loss: 1.0,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PuzzleResult {
+    pub solved: bool,
+    /// Place of the answer in the ranking, `None` for a move that changes nothing
+    pub rank: Option<usize>,
+    /// Score given up against the best move, relative to the best
+    pub loss: f64,
+}
+
+/// Picks out recorded positions where the best move clearly beats the rest.
+#[derive(Debug, Clone, Copy)]
+pub struct PuzzleMiner {
+    min_gap: f64,
+    mistakes_only: bool,
+}
+
+impl Default for PuzzleMiner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PuzzleMiner {
+    /// Expectimax usually rates the best two moves within about 1% of each other, so by default
+    /// a puzzle needs a 5% gap.
+    pub fn new() -> Self {
+        PuzzleMiner {
+            min_gap: 0.05,
+            mistakes_only: false,
+        }
+    }
+
+    pub fn with_min_gap(mut self, min_gap: f64) -> Self {
+        self.min_gap = min_gap;
+        self
+    }
+
+    /// Only keeps positions where the recorded player missed the best move.
+    pub fn with_mistakes_only(mut self, mistakes_only: bool) -> Self {
+        self.mistakes_only = mistakes_only;
+        self
+    }
+
+    /// Scores every recorded position with `ai` and keeps those that make good puzzles,
+    /// skipping repeated boards.
+    pub fn mine(&self, moves: &[GameMove], ai: &mut dyn Agent) -> Vec<Puzzle> {
+        let mut seen = HashSet::new();
+        let mut puzzles = Vec::new();
+        for record in moves {
+            let board = match record.board() {
+                Some(board) => board,
+                None => continue,
+            };
+            if !seen.insert(board) {
+                continue;
+            }
+            let scores = ai.score_moves(board);
+            let best = match best_move(&scores) {
+                Some(best) => best,
+                None => continue,
+            };
+            if self.mistakes_only && record.direction == best {
+                continue;
+            }
+            let gap = match score_gap(&scores) {
+                Some(gap) if gap >= self.min_gap => gap,
+                _ => continue,
+            };
+            puzzles.push(Puzzle {
+                puzzle_id: format!("{}-{}", record.session_id, record.move_number),
+                board_state: player_data::board_to_string(board),
+                session_id: Some(record.session_id.clone()),
+                move_number: Some(record.move_number),
+                played: Some(record.direction),
+                best_move: best,
+                agent: ai.name().to_string(),
+                up_score: scores[0],
+                down_score: scores[1],
+                left_score: scores[2],
+                right_score: scores[3],
+                score_gap: gap,
+            });
+        }
+        puzzles
+    }
+}
+
+/// Relative gap between the best and second best moves, `None` when fewer than two moves are
+/// valid since a forced move is no puzzle.
+pub fn score_gap(scores: &[Option<f64>; 4]) -> Option<f64> {
+    let mut valid: Vec<f64> = scores.iter().flatten().copied().collect();
+    if valid.len() < 2 {
+        return None;
+    }
+    valid.sort_by(|a, b| b.total_cmp(a));
+    Some((valid[0] - valid[1]) / valid[0].abs().max(f64::EPSILON))
+}
+
+/// Whether `filename` is a puzzle file rather than a recording.
+pub fn is_puzzle_file(filename: &str) -> Result<bool, Box<dyn Error>> {
+    let mut header = String::new();
+    BufReader::new(std::fs::File::open(filename)?).read_line(&mut header)?;
+    Ok(header.contains("puzzle_id"))
+}
+
+pub fn load_puzzles(filename: &str) -> Result<Vec<Puzzle>, Box<dyn Error>> {
+    let mut reader = csv::Reader::from_path(filename)?;
+    let mut puzzles = Vec::new();
+    for result in reader.deserialize() {
+        let puzzle: Puzzle = result?;
+        if puzzle.board().is_none() {
+            return Err(format!("Puzzle {}: malformed board '{}'", puzzle.puzzle_id, puzzle.board_state).into());
+        }
+        puzzles.push(puzzle);
+    }
+    Ok(puzzles)
+}
+
+pub fn save_puzzles(filename: &str, puzzles: &[Puzzle]) -> Result<(), Box<dyn Error>> {
+    let mut writer = csv::Writer::from_path(filename)?;
+    for puzzle in puzzles {
+        writer.serialize(puzzle)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema;
+
+    // Scores every position the same way, so the test controls the ranking
+    struct FixedAgent([Option<f64>; 4]);
+
+    impl Agent for FixedAgent {
+        fn name(&self) -> &str {
+            "fixed"
+        }
+
+        fn score_moves(&mut self, _board: Board) -> [Option<f64>; 4] {
+            self.0
+        }
+    }
+
+    #[test]
+    fn it_mines_and_grades_puzzles() {
+        crate::engine::new();
+        let moves = schema::read_moves("deploy/data/game_moves_20250801_162156_oej58.csv").unwrap();
+
+        let mut close = FixedAgent([Some(100.), Some(99.), None, Some(50.)]);
+        assert!(PuzzleMiner::new().mine(&moves[..10], &mut close).is_empty());
+
+        let mut clear = FixedAgent([Some(100.), Some(80.), None, Some(50.)]);
+        let puzzles = PuzzleMiner::new().mine(&moves[..10], &mut clear);
+        assert_eq!(puzzles.len(), 10);
+        assert!((puzzles[0].score_gap - 0.2).abs() < 1e-9);
+        let mistakes = PuzzleMiner::new().with_mistakes_only(true).mine(&moves[..10], &mut clear);
+        assert!(mistakes.iter().all(|p| p.played != Some(Move::Up)));
+
+        let puzzle = &puzzles[0];
+        assert_eq!(puzzle.best_move, Move::Up);
+        assert!(puzzle.grade(Move::Up).solved);
+        let result = puzzle.grade(Move::Right);
+        assert_eq!((result.solved, result.rank), (false, Some(3)));
+        assert!((result.loss - 0.5).abs() < 1e-9);
+        assert_eq!(puzzle.grade(Move::Left).rank, None);
+
+        let filename = std::env::temp_dir().join(format!("ai_2048_puzzles_{}.csv", std::process::id()));
+        let filename = filename.to_str().unwrap();
+        save_puzzles(filename, &puzzles).unwrap();
+        assert!(is_puzzle_file(filename).unwrap());
+        let loaded = load_puzzles(filename).unwrap();
+        std::fs::remove_file(filename).unwrap();
+        assert_eq!(loaded, puzzles);
+        assert_eq!(loaded[0].agent, "fixed");
+    }
+}
@@ -0,0 +1,168 @@
+use crate::{ai_analysis_lines, create_frontend, PlayerInput, Turn};
+use ai_2048::agent::{self, Agent, NTupleAgent, AGENT_NAMES};
+use ai_2048::engine as GameEngine;
+use ai_2048::ntuple;
+use ai_2048::player_data::GameMode;
+use ai_2048::puzzle::{self, Puzzle, PuzzleMiner};
+use ai_2048::timer::GameTimer;
+use std::error::Error;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// `ai-2048 puzzle [FILE]`: asks for the best move in positions where one clearly stands out.
+pub fn run(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let mut filename = String::from("puzzles.csv");
+    let mut agent_name = String::from("expectimax");
+    let mut count = None;
+    let mut line_mode = false;
+    let mut ntuple_weights = None;
+
+    let mut idx = 0;
+    while idx < args.len() {
+        if !args[idx].starts_with("--") {
+            filename = args[idx].clone();
+            idx += 1;
+            continue;
+        }
+        if args[idx] == "--line-mode" {
+            line_mode = true;
+            idx += 1;
+            continue;
+        }
+        let value = args.get(idx + 1).ok_or_else(|| format!("Missing value for {}", args[idx]));
+        match args[idx].as_str() {
+            "--agent" => {
+                let name = value?;
+                if !AGENT_NAMES.contains(&name.as_str()) {
+                    return Err(format!("Unknown agent: {}", name).into());
+                }
+                agent_name = name.clone();
+            }
+            "--ntuple-weights" => ntuple_weights = Some(value?.clone()),
+            "--count" => count = Some(value?.parse::<usize>().map_err(|_| "--count must be a number")?),
+            other => return Err(format!("Unknown option: {}", other).into()),
+        }
+        idx += 2;
+    }
+    if ntuple_weights.is_some() && agent_name != "ntuple" {
+        return Err(format!("--ntuple-weights only applies to the ntuple agent, not {}", agent_name).into());
+    }
+    if !Path::new(&filename).exists() {
+        return Err(format!(
+            "{} not found; mine some with `cargo run --bin analyze puzzles DIR` or pass a recording",
+            filename
+        )
+        .into());
+    }
+
+    GameEngine::new();
+    // A recording is turned into puzzles on the spot, from the positions its player got wrong
+    let mut puzzles = if puzzle::is_puzzle_file(&filename)? {
+        puzzle::load_puzzles(&filename)?
+    } else {
+        println!("Looking for puzzles among the mistakes in {}...", filename);
+        let moves = ai_2048::schema::read_moves(&filename)?;
+        let mut ai: Box<dyn Agent> = match agent_name.as_str() {
+            "ntuple" => Box::new(NTupleAgent::load(ntuple_weights.as_deref().unwrap_or(ntuple::DEFAULT_WEIGHTS_FILE))?),
+            name => agent::create(name).expect("Agent names are checked when parsing"),
+        };
+        PuzzleMiner::new().with_mistakes_only(true).mine(&moves, ai.as_mut())
+    };
+    if let Some(count) = count {
+        puzzles.truncate(count);
+    }
+    if puzzles.is_empty() {
+        return Err(format!("No puzzles found in {}", filename).into());
+    }
+
+    let mut ui = create_frontend(line_mode);
+    let timer = GameTimer::new(GameMode::Normal);
+    let mut results = Vec::new();
+    let mut thinking = Duration::ZERO;
+    'puzzles: for (idx, puzzle) in puzzles.iter().enumerate() {
+        let board = puzzle.board().expect("Puzzle boards are checked when loading");
+        let mut turn = Turn {
+            board,
+            status: format!("Puzzle {} of {} | Find the best move", idx + 1, puzzles.len()),
+            analysis: Vec::new(),
+            autoplay: None,
+            hints: false,
+        };
+        let shown_at = Instant::now();
+        let answer = loop {
+            ui.show(&turn, &timer);
+            match ui.next_input(&timer) {
+                PlayerInput::Move(answer) if GameEngine::shift(board, answer) == board => {
+                    ui.notify(&format!("{:?} does not change the board, try another move.", answer));
+                }
+                PlayerInput::Move(answer) => break answer,
+                PlayerInput::Hint => ui.notify("There are no hints in puzzles."),
+                PlayerInput::Quit | PlayerInput::TimeUp => break 'puzzles,
+            }
+        };
+        thinking += shown_at.elapsed();
+
+        let result = puzzle.grade(answer);
+        results.push(result);
+        ui.notify(&feedback(puzzle, answer));
+        turn.status = format!(
+            "Puzzle {} of {} | {} | Any move for {}",
+            idx + 1,
+            puzzles.len(),
+            if result.solved { "Solved" } else { "Missed" },
+            if idx + 1 < puzzles.len() { "the next puzzle" } else { "the results" }
+        );
+        turn.analysis = ranking_lines(puzzle);
+        // The answer stays on screen until the player moves on
+        ui.show(&turn, &timer);
+        if let PlayerInput::Quit | PlayerInput::TimeUp = ui.next_input(&timer) {
+            break;
+        }
+    }
+    drop(ui);
+
+    let solved = results.iter().filter(|r| r.solved).count();
+    println!("\n=== Puzzle Results ===");
+    println!("Solved: {} of {}", solved, results.len());
+    if !results.is_empty() {
+        let loss = results.iter().map(|r| r.loss).sum::<f64>() / results.len() as f64;
+        println!("Average score given up: {:.2}%", loss * 100.0);
+        println!("Average time per puzzle: {:.1}s", thinking.as_secs_f64() / results.len() as f64);
+    }
+    Ok(())
+}
+
+pub fn print_usage() {
+    println!("Usage: cargo run --release -- puzzle [FILE] [options]");
+    println!("  FILE            Puzzle file from `analyze puzzles`, or a recording to take puzzles from (default puzzles.csv)");
+    println!("  --agent NAME    AI that finds puzzles in a recording (default expectimax)");
+    println!("  --ntuple-weights PATH");
+    println!("                  Network for the ntuple agent (default {})", ntuple::DEFAULT_WEIGHTS_FILE);
+    println!("  --count N       Number of puzzles to play (default all)");
+    println!("  --line-mode     Type moves and press Enter instead of using the full-screen UI");
+}
+
+fn feedback(puzzle: &Puzzle, answer: GameEngine::Move) -> String {
+    let result = puzzle.grade(answer);
+    let mut text = match result.rank {
+        _ if result.solved => format!("Correct! {:?} is the best move.", answer),
+        Some(rank) => format!(
+            "Not quite: {:?} ranks #{} and gives up {:.2}%. The best move is {:?}.",
+            answer,
+            rank,
+            result.loss * 100.0,
+            puzzle.best_move
+        ),
+        None => format!("{:?} does not change the board. The best move is {:?}.", answer, puzzle.best_move),
+    };
+    if let Some(played) = puzzle.played {
+        text.push_str(&format!(" The recorded game went {:?}.", played));
+    }
+    text
+}
+
+// The ranking the answer was graded against, as shown next to a game
+fn ranking_lines(puzzle: &Puzzle) -> Vec<String> {
+    let board = puzzle.board().expect("Puzzle boards are checked when loading");
+    ai_analysis_lines(&puzzle.agent, board, &puzzle.scores())
+}
@@ -0,0 +1,446 @@
+use crate::tui::{self, Screen};
+use ai_2048::agent::{self, best_move, Agent, NTupleAgent, AGENT_NAMES};
+use ai_2048::engine as GameEngine;
+use ai_2048::engine::{Board, Move, MOVES};
+use ai_2048::ntuple;
+use ai_2048::schema::{self, GameMove};
+use ai_2048::validation::{self, TransitionError};
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind};
+use std::error::Error;
+use std::io::{self, BufRead, IsTerminal, Write};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Command {
+    Next,
+    Previous,
+    NextBad,
+    PreviousBad,
+    First,
+    Last,
+    Quit,
+}
+
+/// A recorded move with the boards around it as the engine plays it out.
+struct Step {
+    record: GameMove,
+    before: Board,
+    /// The board once the move is made, before the next tile appears
+    after: Board,
+    /// Why the next recorded board cannot follow from this move, if it cannot
+    break_after: Option<TransitionError>,
+}
+
+/// What is shown for a step: text above the boards, the boards, text below them.
+struct Frame {
+    header: Vec<String>,
+    boards: [Board; 2],
+    details: Vec<String>,
+}
+
+struct Replay {
+    steps: Vec<Step>,
+    ai: Box<dyn Agent>,
+    // Scored when the step is first shown, as the search is slow
+    engine_scores: Vec<Option<[Option<f64>; 4]>>,
+    position: usize,
+    message: Option<String>,
+}
+
+impl Replay {
+    fn new(records: Vec<GameMove>, ai: Box<dyn Agent>) -> Result<Self, String> {
+        let mut steps = Vec::with_capacity(records.len());
+        for record in records {
+            let before = record
+                .board()
+                .ok_or_else(|| format!("Move {}: malformed board '{}'", record.move_number, record.board_state))?;
+            let after = GameEngine::shift(before, record.direction);
+            steps.push(Step {
+                record,
+                before,
+                after,
+                break_after: None,
+            });
+        }
+        for idx in 1..steps.len() {
+            let (step, next) = (&steps[idx - 1], &steps[idx]);
+            let break_after = validation::reconstruct_spawn(step.before, step.record.direction, next.before).err();
+            steps[idx - 1].break_after = break_after;
+        }
+        if steps.is_empty() {
+            return Err(String::from("The recording has no moves"));
+        }
+        Ok(Replay {
+            engine_scores: vec![None; steps.len()],
+            steps,
+            ai,
+            position: 0,
+            message: None,
+        })
+    }
+
+    /// Moves through the game, returning false once the viewer quits.
+    fn apply(&mut self, command: Command) -> bool {
+        self.message = None;
+        let last = self.steps.len() - 1;
+        let bad = |step: &Step| step.record.is_bad_move;
+        match command {
+            Command::Next => self.position = (self.position + 1).min(last),
+            Command::Previous => self.position = self.position.saturating_sub(1),
+            Command::NextBad => match self.steps.iter().skip(self.position + 1).position(bad) {
+                Some(offset) => self.position += offset + 1,
+                None => self.message = Some(String::from("No later bad moves")),
+            },
+            Command::PreviousBad => match self.steps[..self.position].iter().rposition(bad) {
+                Some(position) => self.position = position,
+                None => self.message = Some(String::from("No earlier bad moves")),
+            },
+            Command::First => self.position = 0,
+            Command::Last => self.position = last,
+            Command::Quit => return false,
+        }
+        true
+    }
+
+    fn frame(&mut self) -> Frame {
+        let position = self.position;
+        if self.engine_scores[position].is_none() {
+            self.engine_scores[position] = Some(self.ai.score_moves(self.steps[position].before));
+        }
+        let engine_scores = self.engine_scores[position].unwrap();
+        let step = &self.steps[position];
+        let record = &step.record;
+        let bad_moves = self.steps.iter().filter(|step| step.record.is_bad_move).count();
+
+        let mut header = vec![format!(
+            "=== Replay of {} ({}, {}) ===",
+            record.session_id,
+            record.player_name.as_deref().filter(|name| !name.is_empty()).unwrap_or("unnamed"),
+            record.game_mode.as_deref().unwrap_or("normal")
+        )];
+        header.push(format!(
+            "Move {} of {} | {} bad moves in the game",
+            position + 1,
+            self.steps.len(),
+            bad_moves
+        ));
+        header.push(String::new());
+        let before = format!("Before move #{} (score {})", record.move_number, record.game_score);
+        header.push(format!("{:<41}After {:?}", before, record.direction));
+
+        let mut details = Vec::new();
+        let mut played = format!("Played: {:?} in {}ms", record.direction, record.time_taken_ms);
+        if let Some(grade) = record.move_grade {
+            played.push_str(&format!(", graded {}", grade));
+        }
+        if let Some(difficulty) = record.difficulty {
+            played.push_str(&format!(", {} decision", difficulty));
+        }
+        if record.is_bad_move {
+            played.push_str(" [BAD MOVE]");
+        }
+        details.push(played);
+        match &step.break_after {
+            _ if step.after == step.before => {
+                details.push(String::from("The engine finds that this move does not change the board"))
+            }
+            Some(error) => details.push(format!(
+                "The next recorded board does not follow from this move: {}",
+                error
+            )),
+            None => {}
+        }
+        let recorded = record.scores();
+        if recorded.iter().any(Option::is_some) {
+            details.push(format!("Recorded scores: {}", format_scores(&recorded, record.direction)));
+        } else {
+            details.push(String::from("Recorded scores: none"));
+        }
+        details.push(format!(
+            "{} scores: {}",
+            self.ai.name(),
+            format_scores(&engine_scores, record.direction)
+        ));
+        let played_idx = MOVES.iter().position(|&direction| direction == record.direction).unwrap();
+        let best = engine_scores.iter().flatten().copied().reduce(f64::max);
+        if let (Some(best), Some(chosen)) = (best, engine_scores[played_idx]) {
+            let loss = (best - chosen) / best.abs().max(f64::EPSILON);
+            match best_move(&engine_scores) {
+                Some(engine_move) if chosen < best => details.push(format!(
+                    "{} prefers {:?}; the move played gives up {:.2}%",
+                    self.ai.name(),
+                    engine_move,
+                    loss * 100.0
+                )),
+                _ => details.push(format!("{} agrees with the move played", self.ai.name())),
+            }
+        }
+        if let Some(message) = &self.message {
+            details.push(String::new());
+            details.push(message.clone());
+        }
+
+        Frame {
+            header,
+            boards: [step.before, step.after],
+            details,
+        }
+    }
+}
+
+/// `ai-2048 replay FILE`: steps through a recorded CLI or web game.
+pub fn run(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let mut filename = None;
+    let mut agent_name = String::from("expectimax");
+    let mut session = None;
+    let mut line_mode = false;
+    let mut ntuple_weights = None;
+
+    let mut idx = 0;
+    while idx < args.len() {
+        if !args[idx].starts_with("--") {
+            filename = Some(args[idx].clone());
+            idx += 1;
+            continue;
+        }
+        if args[idx] == "--line-mode" {
+            line_mode = true;
+            idx += 1;
+            continue;
+        }
+        let value = args.get(idx + 1).ok_or_else(|| format!("Missing value for {}", args[idx]));
+        match args[idx].as_str() {
+            "--agent" => {
+                let name = value?;
+                if !AGENT_NAMES.contains(&name.as_str()) {
+                    return Err(format!("Unknown agent: {}", name).into());
+                }
+                agent_name = name.clone();
+            }
+            "--ntuple-weights" => ntuple_weights = Some(value?.clone()),
+            "--session" => session = Some(value?.clone()),
+            other => return Err(format!("Unknown option: {}", other).into()),
+        }
+        idx += 2;
+    }
+    let filename = filename.ok_or("Missing moves file to replay")?;
+    if ntuple_weights.is_some() && agent_name != "ntuple" {
+        return Err(format!("--ntuple-weights only applies to the ntuple agent, not {}", agent_name).into());
+    }
+
+    // Files holding several games are replayed one session at a time
+    let mut moves = schema::read_moves(&filename)?;
+    let session = match session {
+        Some(session) => session,
+        None => moves.first().map(|m| m.session_id.clone()).unwrap_or_default(),
+    };
+    moves.retain(|m| m.session_id == session);
+
+    GameEngine::new();
+    let ai: Box<dyn Agent> = match agent_name.as_str() {
+        "ntuple" => Box::new(NTupleAgent::load(ntuple_weights.as_deref().unwrap_or(ntuple::DEFAULT_WEIGHTS_FILE))?),
+        name => agent::create(name).expect("Agent names are checked when parsing"),
+    };
+    let mut replay = Replay::new(moves, ai)?;
+
+    if !line_mode && io::stdin().is_terminal() && io::stdout().is_terminal() {
+        let mut screen = Screen::new()?;
+        loop {
+            let frame = replay.frame();
+            draw(&mut screen, &frame)?;
+            let command = loop {
+                match event::read()? {
+                    Event::Key(key) if key.kind == KeyEventKind::Press => {
+                        if let Some(command) = key_command(key) {
+                            break command;
+                        }
+                    }
+                    Event::Resize(_, _) => draw(&mut screen, &frame)?,
+                    _ => {}
+                }
+            };
+            if !replay.apply(command) {
+                return Ok(());
+            }
+        }
+    }
+
+    let mut lines = io::stdin().lock().lines();
+    loop {
+        let frame = replay.frame();
+        for line in &frame.header {
+            println!("{}", line);
+        }
+        println!("{}", side_by_side(&GameEngine::to_str(frame.boards[0]), &GameEngine::to_str(frame.boards[1])));
+        for line in &frame.details {
+            println!("{}", line);
+        }
+        print!("\n[n]ext, [p]revious, []] next bad, [[] previous bad, [f]irst, [l]ast, [q]uit: ");
+        io::stdout().flush()?;
+        let command = match lines.next() {
+            Some(line) => match line_command(&line?) {
+                Some(command) => command,
+                None => {
+                    println!("Unknown command");
+                    continue;
+                }
+            },
+            None => Command::Quit,
+        };
+        println!();
+        if !replay.apply(command) {
+            return Ok(());
+        }
+    }
+}
+
+pub fn print_usage() {
+    println!("Usage: cargo run --release -- replay FILE [options]");
+    println!("  --agent NAME    AI that scores each position again (default expectimax)");
+    println!("  --ntuple-weights PATH");
+    println!("                  Network for the ntuple agent (default {})", ntuple::DEFAULT_WEIGHTS_FILE);
+    println!("  --session ID    Game to replay from a file holding several (default the first)");
+    println!("  --line-mode     Type commands and press Enter instead of using the full-screen viewer");
+}
+
+fn draw(screen: &mut Screen, frame: &Frame) -> io::Result<()> {
+    let mut row = 0;
+    screen.clear()?;
+    for line in &frame.header {
+        screen.line(&mut row, line)?;
+    }
+    screen.boards(&mut row, &frame.boards)?;
+    screen.line(&mut row, "")?;
+    for line in &frame.details {
+        screen.line(&mut row, line)?;
+    }
+    screen.line(&mut row, "")?;
+    screen.line(
+        &mut row,
+        "←/→ step, [ and ] previous or next bad move, Home/End first or last move, Q or Esc to quit",
+    )?;
+    screen.flush()
+}
+
+fn key_command(key: KeyEvent) -> Option<Command> {
+    let command = match key.code {
+        _ if tui::is_interrupt(&key) => Command::Quit,
+        KeyCode::Right | KeyCode::Char('n') | KeyCode::Char(' ') => Command::Next,
+        KeyCode::Left | KeyCode::Char('p') => Command::Previous,
+        KeyCode::Char(']') => Command::NextBad,
+        KeyCode::Char('[') => Command::PreviousBad,
+        KeyCode::Home => Command::First,
+        KeyCode::End => Command::Last,
+        KeyCode::Char('q') | KeyCode::Char('Q') | KeyCode::Esc => Command::Quit,
+        _ => return None,
+    };
+    Some(command)
+}
+
+fn line_command(line: &str) -> Option<Command> {
+    let command = match line.trim().to_lowercase().as_str() {
+        "n" | "" => Command::Next,
+        "p" => Command::Previous,
+        "]" => Command::NextBad,
+        "[" => Command::PreviousBad,
+        "f" => Command::First,
+        "l" => Command::Last,
+        "q" | "quit" | "exit" => Command::Quit,
+        _ => return None,
+    };
+    Some(command)
+}
+
+// Scores in `[Up, Down, Left, Right]` order, starring the best and marking the move played
+fn format_scores(scores: &[Option<f64>; 4], played: Move) -> String {
+    let best = best_move(scores);
+    MOVES
+        .iter()
+        .zip(scores.iter())
+        .map(|(&direction, score)| {
+            let score = score.map_or_else(|| String::from("-"), |score| format!("{:.1}", score));
+            let star = if Some(direction) == best { "★" } else { "" };
+            let played = if direction == played { " (played)" } else { "" };
+            format!("{}{:?} {}{}", star, direction, score, played)
+        })
+        .collect::<Vec<_>>()
+        .join(" | ")
+}
+
+fn side_by_side(left: &str, right: &str) -> String {
+    let width = left.lines().map(|line| line.chars().count()).max().unwrap_or(0) + 4;
+    left.lines()
+        .zip(right.lines())
+        .map(|(left, right)| format!("{:<width$}{}", left, right, width = width))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ai_2048::agent::GreedyAgent;
+
+    fn replay(moves: usize, bad: &[usize]) -> Replay {
+        GameEngine::new();
+        let mut records = schema::read_moves("deploy/data/game_moves_20250801_162156_oej58.csv").unwrap();
+        records.truncate(moves);
+        for (idx, record) in records.iter_mut().enumerate() {
+            record.is_bad_move = bad.contains(&idx);
+        }
+        Replay::new(records, Box::new(GreedyAgent)).unwrap()
+    }
+
+    #[test]
+    fn it_steps_between_bad_moves() {
+        let mut replay = replay(6, &[1, 4]);
+        assert!(replay.apply(Command::PreviousBad));
+        assert_eq!((replay.position, replay.message.as_deref()), (0, Some("No earlier bad moves")));
+        replay.apply(Command::NextBad);
+        assert_eq!((replay.position, replay.message.as_deref()), (1, None));
+        replay.apply(Command::NextBad);
+        assert_eq!(replay.position, 4);
+        replay.apply(Command::NextBad);
+        assert_eq!((replay.position, replay.message.as_deref()), (4, Some("No later bad moves")));
+        replay.apply(Command::PreviousBad);
+        assert_eq!(replay.position, 1);
+
+        replay.apply(Command::Last);
+        replay.apply(Command::Next);
+        assert_eq!(replay.position, 5);
+        replay.apply(Command::First);
+        replay.apply(Command::Previous);
+        assert_eq!(replay.position, 0);
+        assert!(!replay.apply(Command::Quit));
+    }
+
+    #[test]
+    fn it_flags_boards_that_do_not_follow() {
+        let mut replay = replay(6, &[]);
+        assert!(replay.steps.iter().all(|step| step.break_after.is_none()));
+
+        GameEngine::new();
+        let mut records = schema::read_moves("deploy/data/game_moves_20250801_162156_oej58.csv").unwrap();
+        records.remove(3);
+        records.truncate(5);
+        replay = Replay::new(records, Box::new(GreedyAgent)).unwrap();
+        let breaks: Vec<bool> = replay.steps.iter().map(|step| step.break_after.is_some()).collect();
+        assert_eq!(breaks, [false, false, true, false, false]);
+        replay.apply(Command::Next);
+        replay.apply(Command::Next);
+        let details = replay.frame().details;
+        assert!(details.iter().any(|line| line.starts_with("The next recorded board does not follow")));
+    }
+
+    #[test]
+    fn it_reads_line_commands() {
+        assert_eq!(line_command(""), Some(Command::Next));
+        assert_eq!(line_command(" N "), Some(Command::Next));
+        assert_eq!(line_command("p"), Some(Command::Previous));
+        assert_eq!(line_command("]"), Some(Command::NextBad));
+        assert_eq!(line_command("["), Some(Command::PreviousBad));
+        assert_eq!(line_command("f"), Some(Command::First));
+        assert_eq!(line_command("L"), Some(Command::Last));
+        assert_eq!(line_command("exit"), Some(Command::Quit));
+        assert_eq!(line_command("x"), None);
+    }
+}
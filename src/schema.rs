@@ -0,0 +1,317 @@
+//! The canonical move record shared by every recording, and readers for the older formats.
+//!
+//! Version 1 holds what both the CLI and the web frontend know about a move. Derived figures
+//! such as the best score or the difficulty evidence are left out because they follow from the
+//! board and the scores.
+//!
+//! Version 2 adds the coaching mode and whether the player asked for a hint. Both are empty in
+//! version 1 files and in web recordings.
+//!
+//! Version 3 adds the agent that made the move in autoplay, so that `player_name` is always the
+//! person playing. It is empty in older files.
+
+use crate::difficulty::Difficulty;
+use crate::engine::{Board, Move};
+use crate::move_quality::MoveGrade;
+use crate::player_data::{self, CoachingMode, MoveRecord};
+use crate::web_data::{self, WebMoveRecord};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::error::Error;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+pub const SCHEMA_VERSION: u32 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Source {
+    Cli,
+    Web,
+}
+
+/// Formats `read_moves` understands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// Files written by `write_moves`
+    Canonical,
+    /// `player_moves_<session>.csv` from the CLI before the canonical schema
+    CliLegacy,
+    /// `game_moves_<session>.csv` from the web frontend
+    WebCsv,
+    /// `game_session_<session>.json` from the web frontend
+    WebJson,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GameMove {
+    pub schema_version: u32,
+    pub source: Source,
+    pub session_id: String,
+    pub player_name: Option<String>,
+    pub game_mode: Option<String>,
+    pub move_number: u32,
+    pub timestamp: String,
+    /// Tile exponents before the move, row by row
+    pub board_state: String,
+    pub direction: Move,
+    pub time_taken_ms: u64,
+    /// Game score before the move
+    pub game_score: u64,
+    /// Scores of the AI that watched the move, empty for invalid moves or when none did
+    pub up_score: Option<f64>,
+    pub down_score: Option<f64>,
+    pub left_score: Option<f64>,
+    pub right_score: Option<f64>,
+    pub is_bad_move: bool,
+    pub difficulty: Option<Difficulty>,
+    pub move_grade: Option<MoveGrade>,
+    pub expected_score_loss: Option<f64>,
+    pub win_probability_loss: Option<f64>,
+    /// Tile counts the web frontend keeps, empty for CLI games
+    pub tiles_added: Option<u32>,
+    pub tiles_removed: Option<u32>,
+    #[serde(default)]
+    pub coaching: Option<CoachingMode>,
+    #[serde(default)]
+    pub hint_requested: Option<bool>,
+    /// The agent that made the move in autoplay, empty for the player's own moves
+    #[serde(default)]
+    pub agent: Option<String>,
+}
+
+impl GameMove {
+    pub fn board(&self) -> Option<Board> {
+        player_data::parse_board(&self.board_state)
+    }
+
+    /// Scores in `[Up, Down, Left, Right]` order.
+    pub fn scores(&self) -> [Option<f64>; 4] {
+        [self.up_score, self.down_score, self.left_score, self.right_score]
+    }
+
+    pub fn best_score(&self) -> Option<f64> {
+        self.scores().iter().flatten().copied().reduce(f64::max)
+    }
+
+    pub fn chosen_score(&self) -> Option<f64> {
+        match self.direction {
+            Move::Up => self.up_score,
+            Move::Down => self.down_score,
+            Move::Left => self.left_score,
+            Move::Right => self.right_score,
+        }
+    }
+
+    /// Converts a row of a legacy CLI moves file. Invalid moves were scored as -1 there.
+    pub fn from_cli(record: &MoveRecord, session_id: &str) -> Result<GameMove, String> {
+        let board = record
+            .board()
+            .ok_or_else(|| format!("Move {}: malformed board '{}'", record.move_number, record.board_state))?;
+        let direction = record
+            .chosen_move()
+            .ok_or_else(|| format!("Move {}: unknown direction '{}'", record.move_number, record.move_chosen))?;
+        let score = |score: f64| if score < 0.0 { None } else { Some(score) };
+        Ok(GameMove {
+            schema_version: SCHEMA_VERSION,
+            source: Source::Cli,
+            session_id: session_id.to_string(),
+            player_name: None,
+            game_mode: None,
+            move_number: record.move_number,
+            timestamp: record.timestamp.clone(),
+            board_state: player_data::board_to_string(board),
+            direction,
+            time_taken_ms: record.time_taken_ms,
+            game_score: record.game_score,
+            up_score: score(record.up_score),
+            down_score: score(record.down_score),
+            left_score: score(record.left_score),
+            right_score: score(record.right_score),
+            is_bad_move: record.is_bad_move,
+            difficulty: record.difficulty,
+            move_grade: record.move_grade,
+            expected_score_loss: None,
+            win_probability_loss: None,
+            tiles_added: None,
+            tiles_removed: None,
+            coaching: None,
+            hint_requested: None,
+            agent: None,
+        })
+    }
+
+    /// Converts a web move. The web records the score after the move, so the score before it
+    /// is passed in from the previous move.
+    pub fn from_web(record: &WebMoveRecord, game_score: u64) -> Result<GameMove, String> {
+        let board = web_data::parse_web_board(&record.board_state)
+            .ok_or_else(|| format!("Move {}: malformed board '{}'", record.move_number, record.board_state))?;
+        let direction = player_data::parse_move(&record.direction)
+            .ok_or_else(|| format!("Move {}: unknown direction '{}'", record.move_number, record.direction))?;
+        Ok(GameMove {
+            schema_version: SCHEMA_VERSION,
+            source: Source::Web,
+            session_id: record.session_id.clone(),
+            player_name: Some(record.player_name.clone()),
+            game_mode: Some(record.game_mode.clone()),
+            move_number: record.move_number,
+            timestamp: record.timestamp.clone(),
+            board_state: player_data::board_to_string(board),
+            direction,
+            time_taken_ms: record.move_time,
+            game_score,
+            up_score: None,
+            down_score: None,
+            left_score: None,
+            right_score: None,
+            is_bad_move: record.is_bad_move,
+            difficulty: None,
+            move_grade: None,
+            expected_score_loss: None,
+            win_probability_loss: None,
+            tiles_added: record.tiles_added,
+            tiles_removed: record.tiles_removed,
+            coaching: None,
+            hint_requested: None,
+            agent: None,
+        })
+    }
+}
+
+/// Works out the format of a moves file from its extension and header.
+pub fn detect_format(filename: &str) -> Result<Format, Box<dyn Error>> {
+    if Path::new(filename).extension().is_some_and(|ext| ext == "json") {
+        return Ok(Format::WebJson);
+    }
+    let mut header = String::new();
+    BufReader::new(std::fs::File::open(filename)?).read_line(&mut header)?;
+    if header.contains("schema_version") {
+        Ok(Format::Canonical)
+    } else if header.contains("move_chosen") {
+        Ok(Format::CliLegacy)
+    } else if header.contains("Session ID") {
+        Ok(Format::WebCsv)
+    } else {
+        Err(format!("Unrecognised moves file: {}", filename).into())
+    }
+}
+
+/// Reads the moves of any recording, whichever format it was written in.
+pub fn read_moves(filename: &str) -> Result<Vec<GameMove>, Box<dyn Error>> {
+    let moves = match detect_format(filename)? {
+        Format::Canonical => {
+            let mut reader = csv::Reader::from_path(filename)?;
+            let mut moves = Vec::new();
+            for result in reader.deserialize() {
+                let mv: GameMove = result?;
+                if mv.schema_version > SCHEMA_VERSION {
+                    return Err(format!(
+                        "{} uses schema version {}, newer than the supported {}",
+                        filename, mv.schema_version, SCHEMA_VERSION
+                    )
+                    .into());
+                }
+                moves.push(mv);
+            }
+            moves
+        }
+        Format::CliLegacy => {
+            let session_id = session_id_from_filename(filename, "player_moves_");
+            player_data::load_moves(filename)?
+                .iter()
+                .map(|record| GameMove::from_cli(record, &session_id))
+                .collect::<Result<_, _>>()?
+        }
+        Format::WebCsv | Format::WebJson => {
+            let mut scores: HashMap<String, u64> = HashMap::new();
+            let mut moves = Vec::new();
+            for record in web_data::load_web_moves(filename)? {
+                let previous = scores.insert(record.session_id.clone(), record.score).unwrap_or(0);
+                moves.push(GameMove::from_web(&record, previous)?);
+            }
+            moves
+        }
+    };
+    Ok(moves)
+}
+
+/// Writes moves in the canonical format.
+pub fn write_moves(filename: &str, moves: &[GameMove]) -> Result<(), Box<dyn Error>> {
+    let mut writer = csv::Writer::from_path(filename)?;
+    for mv in moves {
+        writer.serialize(mv)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+// `player_moves_20240101_120000.csv` holds session 20240101_120000
+fn session_id_from_filename(filename: &str, prefix: &str) -> String {
+    let stem = Path::new(filename).file_stem().unwrap_or_default().to_string_lossy();
+    stem.strip_prefix(prefix).unwrap_or(&stem).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_reads_web_recordings() {
+        let csv_moves = read_moves("deploy/data/game_moves_20250801_162156_oej58.csv").unwrap();
+        let json_moves = read_moves("deploy/data/game_session_20250801_162156_oej58.json").unwrap();
+        assert_eq!(csv_moves, json_moves);
+
+        assert_eq!(csv_moves[0].source, Source::Web);
+        assert_eq!(csv_moves[0].player_name.as_deref(), Some("Simon"));
+        assert_eq!(csv_moves[0].board_state, "0,0,0,0,0,1,0,0,0,0,1,0,0,0,0,0");
+        // The second move starts from the score after the first
+        assert_eq!(csv_moves[0].game_score, 0);
+        assert_eq!(csv_moves[2].game_score, 4);
+    }
+
+    #[test]
+    fn it_round_trips_through_the_canonical_format() {
+        let mut moves = read_moves("deploy/data/game_moves_20250801_162156_oej58.csv").unwrap();
+        moves[0].up_score = Some(1.5);
+        moves[0].difficulty = Some(Difficulty::Hard);
+        moves[0].move_grade = Some(MoveGrade::Blunder);
+
+        let filename = std::env::temp_dir().join(format!("ai_2048_schema_round_trip_{}.csv", std::process::id()));
+        let filename = filename.to_str().unwrap();
+        write_moves(filename, &moves).unwrap();
+        assert_eq!(detect_format(filename).unwrap(), Format::Canonical);
+        let read_back = read_moves(filename).unwrap();
+        std::fs::remove_file(filename).unwrap();
+
+        assert_eq!(read_back, moves);
+        assert_eq!(read_back[0].best_score(), Some(1.5));
+        assert_eq!(read_back[0].chosen_score(), None);
+    }
+
+    #[test]
+    fn it_reads_version_1_files() {
+        let moves = read_moves("deploy/data/game_moves_20250801_162156_oej58.csv").unwrap();
+        let filename = std::env::temp_dir().join(format!("ai_2048_schema_version_1_{}.csv", std::process::id()));
+        let filename = filename.to_str().unwrap();
+        write_moves(filename, &moves[..3]).unwrap();
+
+        // Version 1 had no coaching or agent columns
+        let version_1: String = std::fs::read_to_string(filename)
+            .unwrap()
+            .lines()
+            .map(|line| {
+                let line = line.strip_suffix(",coaching,hint_requested,agent").unwrap_or(line);
+                let line = line.strip_suffix(",,,").unwrap_or(line);
+                format!("{}\n", line.replacen("3,", "1,", 1))
+            })
+            .collect();
+        std::fs::write(filename, version_1).unwrap();
+        let read_back = read_moves(filename).unwrap();
+        std::fs::remove_file(filename).unwrap();
+
+        assert_eq!(read_back.len(), 3);
+        assert_eq!(read_back[0].schema_version, 1);
+        assert_eq!(read_back[0].board_state, moves[0].board_state);
+        assert!(read_back.iter().all(|m| m.coaching.is_none() && m.hint_requested.is_none() && m.agent.is_none()));
+    }
+}
@@ -0,0 +1,171 @@
+use ai_2048::agent::{self, Agent, NTupleAgent, AGENT_NAMES};
+use ai_2048::engine as GameEngine;
+use ai_2048::expectimax::{Expectimax, ExpectimaxMultithread, HeuristicWeights};
+use ai_2048::ntuple;
+use ai_2048::simulation::{self, GameResult, Summary};
+use std::error::Error;
+use std::thread;
+use std::time::Instant;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args: Vec<String> = std::env::args().collect();
+    let mut agent_name = String::from("expectimax");
+    let mut games = 100;
+    let mut first_seed = 0;
+    let mut threads = thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+    let mut depth = None;
+    let mut weights = None;
+    let mut ntuple_weights = None;
+    let mut csv_file = String::from("simulation_results.csv");
+    let mut json_file = None;
+
+    let mut idx = 1;
+    while idx < args.len() {
+        let value = args.get(idx + 1).ok_or_else(|| format!("Missing value for {}", args[idx]));
+        match args[idx].as_str() {
+            "--agent" => agent_name = value?.clone(),
+            "--games" => games = value?.parse()?,
+            "--first-seed" => first_seed = value?.parse()?,
+            "--threads" => threads = value?.parse()?,
+            "--depth" => depth = Some(value?.parse()?),
+            "--weights" => weights = Some(HeuristicWeights::load(value?)?),
+            "--ntuple-weights" => ntuple_weights = Some(value?.clone()),
+            "--csv" => csv_file = value?.clone(),
+            "--json" => json_file = Some(value?.clone()),
+            _ => {
+                print_usage();
+                return Ok(());
+            }
+        }
+        idx += 2;
+    }
+
+    if !AGENT_NAMES.contains(&agent_name.as_str()) {
+        return Err(format!("Unknown agent: {}", agent_name).into());
+    }
+
+    if weights.is_some() && !agent_name.starts_with("expectimax") {
+        return Err(format!("--weights only applies to the expectimax agents, not {}", agent_name).into());
+    }
+    if depth.is_some() && !agent_name.starts_with("expectimax") {
+        return Err(format!("--depth only applies to the expectimax agents, not {}", agent_name).into());
+    }
+    if ntuple_weights.is_some() && agent_name != "ntuple" {
+        return Err(format!("--ntuple-weights only applies to the ntuple agent, not {}", agent_name).into());
+    }
+    // Every game shares the one network
+    let network = if agent_name == "ntuple" {
+        Some(NTupleAgent::load(ntuple_weights.as_deref().unwrap_or(ntuple::DEFAULT_WEIGHTS_FILE))?)
+    } else {
+        None
+    };
+
+    GameEngine::new();
+
+    let make_agent = |seed| -> Box<dyn Agent> {
+        let weights = weights.unwrap_or_default();
+        match agent_name.as_str() {
+            "expectimax" => {
+                let expectimax = Expectimax::with_weights(weights);
+                Box::new(match depth {
+                    Some(depth) => expectimax.with_max_depth(depth),
+                    None => expectimax,
+                })
+            }
+            "expectimax-mt" => {
+                let expectimax = ExpectimaxMultithread::with_weights(weights);
+                Box::new(match depth {
+                    Some(depth) => expectimax.with_max_depth(depth),
+                    None => expectimax,
+                })
+            }
+            "ntuple" => Box::new(network.clone().expect("The network is loaded for the ntuple agent")),
+            name => agent::create_seeded(name, seed).unwrap(),
+        }
+    };
+
+    println!(
+        "Playing {} games with {} (seeds {}..{}) on {} threads",
+        games,
+        agent_name,
+        first_seed,
+        first_seed + games,
+        threads
+    );
+    let start_time = Instant::now();
+    let seeds: Vec<u64> = (first_seed..first_seed + games).collect();
+    let results = simulation::play_games(make_agent, &seeds, threads);
+    let wall_time = start_time.elapsed().as_secs_f64();
+
+    let summary = simulation::summarize(&results);
+    print_summary(&summary, wall_time);
+
+    write_csv(&results, &csv_file)?;
+    println!("\nPer-game results written to {}", csv_file);
+    if let Some(json_file) = json_file {
+        let json = serde_json::json!({
+            "agent": agent_name,
+            "summary": summary,
+            "games": results,
+        });
+        std::fs::write(&json_file, serde_json::to_string_pretty(&json)?)?;
+        println!("Summary and results written to {}", json_file);
+    }
+
+    Ok(())
+}
+
+fn print_usage() {
+    println!("Usage: cargo run --release --bin simulate -- [options]");
+    println!("  --agent NAME        {} (default expectimax)", AGENT_NAMES.join(", "));
+    println!("  --games N           Number of games (default 100)");
+    println!("  --first-seed N      Seed of the first game, the rest follow on (default 0); the tile");
+    println!("                      spawns and the random, montecarlo and mcts agents are drawn from it");
+    println!("  --threads N         Games played in parallel (default: all cores)");
+    println!("  --depth N           Search depth cap for the expectimax agents");
+    println!("  --weights PATH      Heuristic weights for the expectimax agents, as written by tune");
+    println!("  --ntuple-weights PATH");
+    println!(
+        "                      Network for the ntuple agent, as written by train_ntuple (default {})",
+        ntuple::DEFAULT_WEIGHTS_FILE
+    );
+    println!("  --csv PATH          Per-game results (default simulation_results.csv)");
+    println!("  --json PATH         Also write the summary and results as JSON");
+}
+
+fn print_summary(summary: &Summary, wall_time: f64) {
+    println!("\n=== Simulation Summary ===");
+    println!("Games played: {}", summary.games);
+
+    println!("\n--- Scores ---");
+    println!("Average: {:.0} (std dev {:.0})", summary.average_score, summary.score_std_dev);
+    println!("Median: {}", summary.median_score);
+    println!("Min / Max: {} / {}", summary.min_score, summary.max_score);
+
+    println!("\n--- Highest Tile ---");
+    for (tile, count) in summary.highest_tiles.iter().rev() {
+        println!(
+            "{:>6}: {} ({:.1}%)",
+            tile,
+            count,
+            *count as f64 / summary.games.max(1) as f64 * 100.0
+        );
+    }
+    println!("Reached 2048: {:.1}%", summary.reached_2048 * 100.0);
+    println!("Reached 4096: {:.1}%", summary.reached_4096 * 100.0);
+    println!("Reached 8192: {:.1}%", summary.reached_8192 * 100.0);
+
+    println!("\n--- Speed ---");
+    println!("Average moves per game: {:.1}", summary.average_moves);
+    println!("Moves per second (per thread): {:.0}", summary.moves_per_second);
+    println!("Wall time: {:.1}s", wall_time);
+}
+
+fn write_csv(results: &[GameResult], filename: &str) -> Result<(), Box<dyn Error>> {
+    let mut writer = csv::Writer::from_path(filename)?;
+    for result in results {
+        writer.serialize(result)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
@@ -0,0 +1,179 @@
+use crate::agent::Agent;
+use crate::engine as GameEngine;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Instant;
+
+/// Outcome of one self-play game.
+#[derive(Debug, Clone, Serialize)]
+pub struct GameResult {
+    pub agent: String,
+    pub seed: u64,
+    pub score: u64,
+    pub highest_tile: u64,
+    pub moves: u32,
+    pub duration_ms: f64,
+}
+
+/// Statistics over a batch of games.
+#[derive(Debug, Serialize)]
+pub struct Summary {
+    pub games: usize,
+    pub average_score: f64,
+    pub median_score: u64,
+    pub min_score: u64,
+    pub max_score: u64,
+    pub score_std_dev: f64,
+    /// Number of games ending with each highest tile
+    pub highest_tiles: BTreeMap<u64, usize>,
+    pub reached_2048: f64,
+    pub reached_4096: f64,
+    pub reached_8192: f64,
+    pub average_moves: f64,
+    /// Moves per second of computation, summed over all games
+    pub moves_per_second: f64,
+}
+
+/// Plays a game to the end with the tile spawns drawn from `seed`.
+pub fn play_game(agent: &mut dyn Agent, seed: u64) -> GameResult {
+    let start_time = Instant::now();
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut board = GameEngine::insert_random_tile_with(0, &mut rng);
+    board = GameEngine::insert_random_tile_with(board, &mut rng);
+    let mut moves = 0;
+    while let Some(direction) = agent.choose(board) {
+        board = GameEngine::make_move_with(board, direction, &mut rng);
+        moves += 1;
+    }
+    GameResult {
+        agent: agent.name().to_string(),
+        seed,
+        score: GameEngine::get_score(board),
+        highest_tile: GameEngine::get_highest_tile_val(board),
+        moves,
+        duration_ms: start_time.elapsed().as_secs_f64() * 1000.0,
+    }
+}
+
+/// Plays one game per seed on `threads` threads. Every game gets a fresh agent from
+/// `make_agent`, given the game's `agent_seed`, so agents that draw random numbers play the same
+/// games however the games are shared out between threads.
+///
+/// Results are returned in seed order.
+pub fn play_games<F>(make_agent: F, seeds: &[u64], threads: usize) -> Vec<GameResult>
+where
+    F: Fn(u64) -> Box<dyn Agent> + Sync,
+{
+    let next_idx = AtomicUsize::new(0);
+    let results = Mutex::new(Vec::with_capacity(seeds.len()));
+
+    thread::scope(|scope| {
+        for _ in 0..threads.clamp(1, seeds.len().max(1)) {
+            scope.spawn(|| loop {
+                let idx = next_idx.fetch_add(1, Ordering::SeqCst);
+                if idx >= seeds.len() {
+                    break;
+                }
+                let mut agent = make_agent(agent_seed(seeds[idx]));
+                let result = play_game(agent.as_mut(), seeds[idx]);
+                results.lock().unwrap().push((idx, result));
+            });
+        }
+    });
+
+    let mut results = results.into_inner().unwrap();
+    results.sort_by_key(|(idx, _)| *idx);
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
+/// Seed for the agent's own random numbers in the game played on `game_seed`, drawn apart from
+/// the tile spawns.
+pub fn agent_seed(game_seed: u64) -> u64 {
+    game_seed ^ 0x9e37_79b9_7f4a_7c15
+}
+
+pub fn summarize(results: &[GameResult]) -> Summary {
+    let games = results.len();
+    let count = games.max(1) as f64;
+    let mut scores: Vec<u64> = results.iter().map(|r| r.score).collect();
+    scores.sort_unstable();
+
+    let average_score = scores.iter().sum::<u64>() as f64 / count;
+    let variance = scores
+        .iter()
+        .map(|&score| (score as f64 - average_score).powi(2))
+        .sum::<f64>()
+        / count;
+
+    let mut highest_tiles = BTreeMap::new();
+    for result in results {
+        *highest_tiles.entry(result.highest_tile).or_insert(0) += 1;
+    }
+    let reached = |tile: u64| results.iter().filter(|r| r.highest_tile >= tile).count() as f64 / count;
+
+    let total_moves: u64 = results.iter().map(|r| r.moves as u64).sum();
+    let total_seconds: f64 = results.iter().map(|r| r.duration_ms).sum::<f64>() / 1000.0;
+
+    Summary {
+        games,
+        average_score,
+        median_score: scores.get(games / 2).copied().unwrap_or(0),
+        min_score: scores.first().copied().unwrap_or(0),
+        max_score: scores.last().copied().unwrap_or(0),
+        score_std_dev: variance.sqrt(),
+        highest_tiles,
+        reached_2048: reached(2048),
+        reached_4096: reached(4096),
+        reached_8192: reached(8192),
+        average_moves: total_moves as f64 / count,
+        moves_per_second: if total_seconds > 0. {
+            total_moves as f64 / total_seconds
+        } else {
+            0.
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::{self, CornerAgent};
+
+    #[test]
+    fn it_replays_games_from_a_seed() {
+        GameEngine::new();
+        let first = play_game(&mut CornerAgent, 42);
+        let second = play_game(&mut CornerAgent, 42);
+        assert_eq!((first.score, first.moves), (second.score, second.moves));
+        assert!(first.moves > 0);
+    }
+
+    #[test]
+    fn it_summarizes_parallel_games() {
+        GameEngine::new();
+        let seeds: Vec<u64> = (0..6).collect();
+        let results = play_games(|_| Box::new(CornerAgent), &seeds, 3);
+        assert_eq!(results.iter().map(|r| r.seed).collect::<Vec<_>>(), seeds);
+
+        let summary = summarize(&results);
+        assert_eq!(summary.games, 6);
+        assert_eq!(summary.highest_tiles.values().sum::<usize>(), 6);
+        assert!(summary.min_score <= summary.median_score && summary.median_score <= summary.max_score);
+    }
+
+    #[test]
+    fn it_repeats_games_of_random_agents() {
+        GameEngine::new();
+        let seeds: Vec<u64> = (0..4).collect();
+        let make_agent = |seed| agent::create_seeded("random", seed).unwrap();
+        let scores = |threads| {
+            play_games(make_agent, &seeds, threads).iter().map(|r| (r.score, r.moves)).collect::<Vec<_>>()
+        };
+        assert_eq!(scores(1), scores(3));
+    }
+}
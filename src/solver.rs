@@ -0,0 +1,225 @@
+use crate::engine as GameEngine;
+use crate::engine::{Board, Move, MOVES};
+use std::collections::HashMap;
+
+/// Exact expectimax over every spawn, giving the true probability of reaching a target tile
+/// under optimal play.
+///
+/// Only small problems are tractable: 3x3 boards, or 4x4 boards with a small target (up to 32
+/// or 64). 3x3 boards use the top left 3x3 cells of the usual `Board` encoding and leave the
+/// last row and column empty.
+pub struct Solver {
+    size: usize,
+    target: u64,
+    // Win probability of positions where the player is to move
+    cache: HashMap<Board, f64>,
+}
+
+impl Solver {
+    /// `target_tile` is a tile value such as 256; `size` is 3 or 4.
+    pub fn new(size: usize, target_tile: u64) -> Self {
+        assert!(size == 3 || size == 4, "Only 3x3 and 4x4 boards are supported");
+        assert!(
+            target_tile.is_power_of_two() && target_tile >= 4,
+            "The target must be a tile value"
+        );
+        if size == 4 {
+            GameEngine::new();
+        }
+        Solver {
+            size,
+            target: target_tile.trailing_zeros() as u64,
+            cache: HashMap::new(),
+        }
+    }
+
+    /// Number of positions solved so far.
+    pub fn states(&self) -> usize {
+        self.cache.len()
+    }
+
+    /// Probability of reaching the target from `board` with the player to move.
+    pub fn win_probability(&mut self, board: Board) -> f64 {
+        if self.has_won(board) {
+            return 1.;
+        }
+        if let Some(&probability) = self.cache.get(&board) {
+            return probability;
+        }
+        let probability = self
+            .move_probabilities(board)
+            .iter()
+            .flatten()
+            .fold(0., |best: f64, &probability| best.max(probability));
+        self.cache.insert(board, probability);
+        probability
+    }
+
+    /// Win probability after each move in `[Up, Down, Left, Right]` order, `None` if invalid.
+    pub fn move_probabilities(&mut self, board: Board) -> [Option<f64>; 4] {
+        let mut probabilities = [None; 4];
+        for (probability, &direction) in probabilities.iter_mut().zip(MOVES.iter()) {
+            let afterstate = self.shift(board, direction);
+            if afterstate != board {
+                *probability = Some(self.spawn_probability(afterstate));
+            }
+        }
+        probabilities
+    }
+
+    /// The move with the highest win probability, `None` if the game is over.
+    pub fn best_move(&mut self, board: Board) -> Option<Move> {
+        crate::agent::best_move(&self.move_probabilities(board))
+    }
+
+    /// All moves within `tolerance` of the best win probability.
+    pub fn optimal_moves(&mut self, board: Board, tolerance: f64) -> Vec<Move> {
+        let probabilities = self.move_probabilities(board);
+        let best = probabilities.iter().flatten().fold(0., |best: f64, &p| best.max(p));
+        MOVES
+            .iter()
+            .zip(probabilities.iter())
+            .filter(|(_, probability)| probability.is_some_and(|p| best - p <= tolerance))
+            .map(|(&direction, _)| direction)
+            .collect()
+    }
+
+    // Average over every spawn: each empty cell equally likely, a 2 nine times in ten
+    fn spawn_probability(&mut self, afterstate: Board) -> f64 {
+        if self.has_won(afterstate) {
+            return 1.;
+        }
+        let empty_cells = self.empty_cells(afterstate);
+        let total: f64 = empty_cells
+            .iter()
+            .map(|&shift| {
+                0.9 * self.win_probability(afterstate | (1 << shift))
+                    + 0.1 * self.win_probability(afterstate | (2 << shift))
+            })
+            .sum();
+        total / empty_cells.len() as f64
+    }
+
+    fn has_won(&self, board: Board) -> bool {
+        (0..16).any(|idx| (board >> (4 * idx)) & 0xf >= self.target)
+    }
+
+    // Bit offsets of the empty cells inside the playing area
+    fn empty_cells(&self, board: Board) -> Vec<u64> {
+        let mut cells = Vec::new();
+        for row in 0..self.size {
+            for col in 0..self.size {
+                let shift = cell_shift(row, col);
+                if (board >> shift) & 0xf == 0 {
+                    cells.push(shift);
+                }
+            }
+        }
+        cells
+    }
+
+    fn shift(&self, board: Board, direction: Move) -> Board {
+        if self.size == 4 {
+            return GameEngine::shift(board, direction);
+        }
+        let size = self.size;
+        let mut new_board = 0;
+        for line in 0..size {
+            // Cells of the line, ordered from the side the tiles move towards
+            let mut shifts = [0; 4];
+            let mut tiles = [0; 4];
+            for pos in 0..size {
+                let (row, col) = match direction {
+                    Move::Left => (line, pos),
+                    Move::Right => (line, size - 1 - pos),
+                    Move::Up => (pos, line),
+                    Move::Down => (size - 1 - pos, line),
+                };
+                shifts[pos] = cell_shift(row, col);
+                tiles[pos] = (board >> shifts[pos]) & 0xf;
+            }
+            merge_line(&mut tiles[..size]);
+            for pos in 0..size {
+                new_board |= tiles[pos] << shifts[pos];
+            }
+        }
+        new_board
+    }
+}
+
+fn cell_shift(row: usize, col: usize) -> u64 {
+    60 - 4 * (row * 4 + col) as u64
+}
+
+// Slides the tiles to the front of the line, merging each pair of equal tiles once
+fn merge_line(tiles: &mut [u64]) {
+    let mut len = 0;
+    let mut pending = None;
+    for idx in 0..tiles.len() {
+        let tile = tiles[idx];
+        tiles[idx] = 0;
+        match (tile, pending) {
+            (0, _) => {}
+            (tile, Some(previous)) if previous == tile => {
+                tiles[len] = tile + 1;
+                len += 1;
+                pending = None;
+            }
+            (tile, Some(previous)) => {
+                tiles[len] = previous;
+                len += 1;
+                pending = Some(tile);
+            }
+            (tile, None) => pending = Some(tile),
+        }
+    }
+    if let Some(previous) = pending {
+        tiles[len] = previous;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_shifts_within_the_small_board() {
+        let solver = Solver::new(3, 16);
+        assert_eq!(solver.shift(0x1000_0000_0000_0000, Move::Right), 0x0010_0000_0000_0000);
+        assert_eq!(solver.shift(0x1000_0000_0000_0000, Move::Down), 0x0000_0000_1000_0000);
+        assert_eq!(solver.shift(0x0110_0000_0000_0000, Move::Left), 0x2000_0000_0000_0000);
+        let mut line = [1, 1, 1];
+        merge_line(&mut line);
+        assert_eq!(line, [2, 1, 0]);
+    }
+
+    #[test]
+    fn it_solves_trivial_positions() {
+        let mut solver = Solver::new(3, 4);
+        // Merging the two 2s makes the target straight away
+        assert_eq!(solver.win_probability(0x1100_0000_0000_0000), 1.);
+        // A full board with no merges is lost
+        let mut solver = Solver::new(3, 16);
+        assert_eq!(solver.win_probability(0x1210_2120_1210_0000), 0.);
+    }
+
+    #[test]
+    fn it_ranks_moves_by_win_probability() {
+        let mut solver = Solver::new(3, 16);
+        let board = 0x1200_0000_0000_0000;
+        let probabilities = solver.move_probabilities(board);
+        let best = solver.win_probability(board);
+        assert!(best > 0. && best <= 1.);
+        assert!(probabilities.iter().flatten().all(|&p| p <= best));
+        let best_move = solver.best_move(board).unwrap();
+        assert!(solver.optimal_moves(board, 1e-12).contains(&best_move));
+    }
+
+    #[test]
+    fn it_solves_small_targets_on_the_full_board() {
+        let mut solver = Solver::new(4, 8);
+        let probability = solver.win_probability(0x1212_2121_1212_0000);
+        assert!(probability > 0.9 && probability <= 1.);
+        assert!(solver.states() > 0);
+    }
+}
@@ -0,0 +1,232 @@
+//! Where `PlayerDataRecorder` keeps what it records. The CLI writes files into its data directory
+//! and, built with the `sqlite` feature, a database as well; tests and frontends without a
+//! filesystem keep sessions in memory.
+
+use crate::data_dir::DataDir;
+use crate::player_data::{self, GameSession, SessionRecord};
+use crate::schema::{self, GameMove};
+use std::error::Error;
+use std::fs::File;
+
+/// A recorded game: its session and its moves in the order they were made.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StoredSession {
+    pub session: SessionRecord,
+    pub moves: Vec<GameMove>,
+}
+
+pub trait SessionStore {
+    /// Called as a game starts, with its session so far.
+    fn begin_session(&mut self, _session: &GameSession) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    /// Adds a move as soon as it is made.
+    fn append_move(&mut self, mv: &GameMove) -> Result<(), Box<dyn Error>>;
+
+    /// Adds the session of a game that has ended; its moves were appended already.
+    fn finish_session(&mut self, session: &GameSession) -> Result<(), Box<dyn Error>>;
+
+    /// Every finished session.
+    fn list_sessions(&self) -> Result<Vec<SessionRecord>, Box<dyn Error>>;
+
+    /// A finished session with its moves, `None` for an unknown ID.
+    fn load_session(&self, session_id: &str) -> Result<Option<StoredSession>, Box<dyn Error>>;
+}
+
+/// Recordings in a data directory, laid out as the `data_dir` module describes. Sessions are
+/// listed from the CLI's summary and the web server's alike. One game is recorded at a time.
+pub struct FileStore {
+    data_dir: DataDir,
+    // The moves file of the game being recorded, opened with its first move
+    log: Option<csv::Writer<File>>,
+}
+
+impl FileStore {
+    pub fn new(data_dir: DataDir) -> Self {
+        FileStore { data_dir, log: None }
+    }
+
+    pub fn data_dir(&self) -> &DataDir {
+        &self.data_dir
+    }
+}
+
+impl SessionStore for FileStore {
+    /// Marks the session incomplete until it is finished, so that a game cut short by a crash or
+    /// a closed terminal can be recovered with `player_data::recover_sessions`.
+    fn begin_session(&mut self, session: &GameSession) -> Result<(), Box<dyn Error>> {
+        self.data_dir.create()?;
+        let marker = serde_json::to_string_pretty(session)?;
+        std::fs::write(self.data_dir.incomplete_marker(&session.session_id), marker)?;
+        self.log = Some(csv::Writer::from_path(self.data_dir.cli_moves(&session.session_id))?);
+        Ok(())
+    }
+
+    fn append_move(&mut self, mv: &GameMove) -> Result<(), Box<dyn Error>> {
+        if self.log.is_none() {
+            self.data_dir.create()?;
+            self.log = Some(csv::Writer::from_path(self.data_dir.cli_moves(&mv.session_id))?);
+        }
+        if let Some(log) = &mut self.log {
+            log.serialize(mv)?;
+            log.flush()?;
+        }
+        Ok(())
+    }
+
+    fn finish_session(&mut self, session: &GameSession) -> Result<(), Box<dyn Error>> {
+        let moves_filename = self.data_dir.cli_moves(&session.session_id);
+        match self.log.take() {
+            Some(mut log) => log.flush()?,
+            // A game without moves still gets its (empty) moves file
+            None => {
+                self.data_dir.create()?;
+                schema::write_moves(&moves_filename.to_string_lossy(), &[])?
+            }
+        }
+        player_data::append_session(&self.data_dir, session)?;
+
+        let marker = self.data_dir.incomplete_marker(&session.session_id);
+        if marker.exists() {
+            std::fs::remove_file(marker)?;
+        }
+        Ok(())
+    }
+
+    fn list_sessions(&self) -> Result<Vec<SessionRecord>, Box<dyn Error>> {
+        let mut sessions = Vec::new();
+        for sessions_file in [self.data_dir.cli_summary(), self.data_dir.web_summary()] {
+            if sessions_file.exists() {
+                sessions.extend(player_data::read_session_records(&sessions_file)?);
+            }
+        }
+        Ok(sessions)
+    }
+
+    fn load_session(&self, session_id: &str) -> Result<Option<StoredSession>, Box<dyn Error>> {
+        let session = match self.list_sessions()?.into_iter().find(|s| s.session_id == session_id) {
+            Some(session) => session,
+            None => return Ok(None),
+        };
+        let moves = match [self.data_dir.cli_moves(session_id), self.data_dir.web_moves(session_id)]
+            .iter()
+            .find(|path| path.exists())
+        {
+            Some(path) => schema::read_moves(&path.to_string_lossy())?,
+            None => Vec::new(),
+        };
+        Ok(Some(StoredSession { session, moves }))
+    }
+}
+
+/// Recordings kept in memory and lost with the store.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryStore {
+    sessions: Vec<SessionRecord>,
+    moves: Vec<GameMove>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl SessionStore for MemoryStore {
+    fn append_move(&mut self, mv: &GameMove) -> Result<(), Box<dyn Error>> {
+        self.moves.push(mv.clone());
+        Ok(())
+    }
+
+    fn finish_session(&mut self, session: &GameSession) -> Result<(), Box<dyn Error>> {
+        self.sessions.push(SessionRecord::from(session));
+        Ok(())
+    }
+
+    fn list_sessions(&self) -> Result<Vec<SessionRecord>, Box<dyn Error>> {
+        Ok(self.sessions.clone())
+    }
+
+    fn load_session(&self, session_id: &str) -> Result<Option<StoredSession>, Box<dyn Error>> {
+        let session = self.sessions.iter().find(|s| s.session_id == session_id);
+        Ok(session.map(|session| StoredSession {
+            session: session.clone(),
+            moves: self.moves.iter().filter(|mv| mv.session_id == session_id).cloned().collect(),
+        }))
+    }
+}
+
+/// Records into two stores at once, such as files and a database, and reads from the first.
+pub struct Replicated<P, R> {
+    primary: P,
+    replica: R,
+}
+
+impl<P: SessionStore, R: SessionStore> Replicated<P, R> {
+    pub fn new(primary: P, replica: R) -> Self {
+        Replicated { primary, replica }
+    }
+}
+
+impl<P: SessionStore, R: SessionStore> SessionStore for Replicated<P, R> {
+    fn begin_session(&mut self, session: &GameSession) -> Result<(), Box<dyn Error>> {
+        self.primary.begin_session(session)?;
+        self.replica.begin_session(session)
+    }
+
+    fn append_move(&mut self, mv: &GameMove) -> Result<(), Box<dyn Error>> {
+        self.primary.append_move(mv)?;
+        self.replica.append_move(mv)
+    }
+
+    fn finish_session(&mut self, session: &GameSession) -> Result<(), Box<dyn Error>> {
+        self.primary.finish_session(session)?;
+        self.replica.finish_session(session)
+    }
+
+    fn list_sessions(&self) -> Result<Vec<SessionRecord>, Box<dyn Error>> {
+        self.primary.list_sessions()
+    }
+
+    fn load_session(&self, session_id: &str) -> Result<Option<StoredSession>, Box<dyn Error>> {
+        self.primary.load_session(session_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::Move;
+    use crate::move_quality::MoveQualityAnalyzer;
+    use crate::player_data::PlayerDataRecorder;
+
+    #[test]
+    fn it_records_into_any_store() {
+        crate::engine::new();
+        let mut recorder = PlayerDataRecorder::new()
+            .with_player_name("Ann")
+            .with_store(Replicated::new(MemoryStore::new(), MemoryStore::new()))
+            .with_move_quality(MoveQualityAnalyzer::new(2));
+        recorder.begin().unwrap();
+        let board = 0x1100_0000_0000_0000;
+        recorder.record_move(board, Move::Left, 30, [None, Some(4.), Some(4.), Some(4.)], 0, 1).unwrap();
+        recorder.record_move(0x2000_1000_0000_0000, Move::Up, 50, [Some(4.), None, Some(4.), Some(4.)], 4, 2).unwrap();
+        recorder.save_session_data(4, 4).unwrap();
+
+        let store = recorder.store();
+        let sessions = store.list_sessions().unwrap();
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].player_name.as_deref(), Some("Ann"));
+        assert_eq!((sessions[0].total_moves, sessions[0].complete), (2, Some(true)));
+        let stored = store.load_session(recorder.get_session_id()).unwrap().unwrap();
+        assert_eq!(stored.session, sessions[0]);
+        assert_eq!(stored.moves.iter().map(|mv| mv.move_number).collect::<Vec<_>>(), [1, 2]);
+        assert!(store.load_session("20250801_162156_oej58").unwrap().is_none());
+
+        // The web server's recordings load through the file store
+        let files = FileStore::new(DataDir::new("deploy/data"));
+        let web = files.load_session("20250801_162156_oej58").unwrap().unwrap();
+        assert_eq!((web.session.total_moves as usize, web.moves.len()), (146, 146));
+    }
+}
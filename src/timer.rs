@@ -0,0 +1,143 @@
+use crate::engine::Board;
+use crate::player_data::GameMode;
+use std::time::{Duration, Instant};
+
+/// Length of a countdown game, as in the web frontend.
+pub const COUNTDOWN_SECONDS: u64 = 120;
+
+/// Keeps time for the timed game modes: count-up shows the time taken, countdown ends the game
+/// when its time runs out. Time spent paused, such as while the engine scores a position, is not
+/// counted, so a game's clock does not depend on how fast the machine is.
+pub struct GameTimer {
+    mode: GameMode,
+    start: Instant,
+    limit: Duration,
+    // Time spent paused, not counting the current pause
+    paused: Duration,
+    paused_at: Option<Instant>,
+}
+
+impl GameTimer {
+    pub fn new(mode: GameMode) -> Self {
+        Self::with_limit(mode, Duration::from_secs(COUNTDOWN_SECONDS))
+    }
+
+    /// A timer whose countdown lasts `limit` instead of the usual two minutes.
+    pub fn with_limit(mode: GameMode, limit: Duration) -> Self {
+        GameTimer {
+            mode,
+            start: Instant::now(),
+            limit,
+            paused: Duration::from_secs(0),
+            paused_at: None,
+        }
+    }
+
+    /// Stops the clock until `resume`.
+    pub fn pause(&mut self) {
+        if self.paused_at.is_none() {
+            self.paused_at = Some(Instant::now());
+        }
+    }
+
+    pub fn resume(&mut self) {
+        if let Some(paused_at) = self.paused_at.take() {
+            self.paused += paused_at.elapsed();
+        }
+    }
+
+    // The clock's current time, which stands still while paused
+    fn now(&self) -> Instant {
+        self.paused_at.unwrap_or_else(Instant::now)
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.now().saturating_duration_since(self.start + self.paused)
+    }
+
+    /// When a countdown runs out, `None` in the other modes. Pausing moves it back.
+    pub fn deadline(&self) -> Option<Instant> {
+        match self.mode {
+            GameMode::CountDown => Some(self.start + self.paused + self.limit),
+            _ => None,
+        }
+    }
+
+    pub fn remaining(&self) -> Option<Duration> {
+        self.deadline()
+            .map(|deadline| deadline.saturating_duration_since(self.now()))
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.remaining() == Some(Duration::from_secs(0))
+    }
+
+    /// The clock to show the player, `None` in normal mode.
+    pub fn display(&self) -> Option<String> {
+        match self.mode {
+            GameMode::Normal => None,
+            GameMode::CountUp => Some(format!("Time: {}", format_clock(self.elapsed().as_secs()))),
+            GameMode::CountDown => {
+                // Round up so the clock only shows 00:00 once time is up
+                let remaining = self.remaining().unwrap();
+                let seconds = remaining.as_secs() + (remaining.subsec_nanos() > 0) as u64;
+                Some(format!("Time left: {}", format_clock(seconds)))
+            }
+        }
+    }
+}
+
+/// Score of a countdown game that runs out of time: the sum of its tile values.
+pub fn time_up_score(board: Board) -> u64 {
+    crate::engine::to_vec(board)
+        .iter()
+        .filter(|&&tile| tile > 0)
+        .map(|&tile| 1 << tile)
+        .sum()
+}
+
+fn format_clock(seconds: u64) -> String {
+    format!("{:02}:{:02}", seconds / 60, seconds % 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_counts_down_to_expiry() {
+        let timer = GameTimer::with_limit(GameMode::CountDown, Duration::from_secs(0));
+        assert!(timer.is_expired());
+        assert_eq!(timer.display().unwrap(), "Time left: 00:00");
+
+        let timer = GameTimer::new(GameMode::CountDown);
+        assert!(!timer.is_expired());
+        assert_eq!(timer.display().unwrap(), "Time left: 02:00");
+
+        let timer = GameTimer::new(GameMode::CountUp);
+        assert!(timer.deadline().is_none() && !timer.is_expired());
+        assert_eq!(timer.display().unwrap(), "Time: 00:00");
+        assert!(GameTimer::new(GameMode::Normal).display().is_none());
+    }
+
+    #[test]
+    fn it_does_not_count_paused_time() {
+        let mut timer = GameTimer::with_limit(GameMode::CountDown, Duration::from_millis(100));
+        let deadline = timer.deadline().unwrap();
+        timer.pause();
+        // Engine time, longer than the whole countdown
+        std::thread::sleep(Duration::from_millis(150));
+        assert!(!timer.is_expired());
+        timer.resume();
+        assert!(!timer.is_expired());
+        assert!(timer.deadline().unwrap() >= deadline + Duration::from_millis(150));
+        assert!(timer.elapsed() < Duration::from_millis(100));
+    }
+
+    #[test]
+    fn it_scores_time_up_by_tile_sum() {
+        // 2 + 4 + 2048
+        assert_eq!(time_up_score(0x1200_0000_0000_000b), 2054);
+        assert_eq!(time_up_score(0), 0);
+    }
+}
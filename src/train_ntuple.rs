@@ -0,0 +1,93 @@
+use ai_2048::engine as GameEngine;
+use ai_2048::ntuple::{self, NTupleNetwork};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::error::Error;
+use std::path::Path;
+use std::time::Instant;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args: Vec<String> = std::env::args().collect();
+    let mut games = 100_000;
+    let mut learning_rate = 0.1;
+    let mut seed: u64 = 0;
+    let mut report_every = 1000;
+    let mut weights_file = String::from(ntuple::DEFAULT_WEIGHTS_FILE);
+
+    let mut idx = 1;
+    while idx < args.len() {
+        let value = args.get(idx + 1).ok_or_else(|| format!("Missing value for {}", args[idx]));
+        match args[idx].as_str() {
+            "--games" => games = value?.parse()?,
+            "--learning-rate" => learning_rate = value?.parse()?,
+            "--seed" => seed = value?.parse()?,
+            "--report-every" => report_every = value?.parse::<u64>()?.max(1),
+            "--weights" => weights_file = value?.clone(),
+            _ => {
+                print_usage();
+                return Ok(());
+            }
+        }
+        idx += 2;
+    }
+
+    GameEngine::new();
+
+    let mut network = if Path::new(&weights_file).exists() {
+        println!("Continuing training from {}", weights_file);
+        NTupleNetwork::load(&weights_file)?
+    } else {
+        NTupleNetwork::default()
+    };
+
+    // Game N is played on seed + N, so resumed training goes on to new games
+    let first_game = network.games_trained();
+    if first_game > 0 {
+        println!("{} games trained so far", first_game);
+    }
+    let mut total_score = 0;
+    let mut best_score = 0;
+    let mut reached_2048 = 0;
+    let start_time = Instant::now();
+
+    for game in 1..=games {
+        let mut rng = StdRng::seed_from_u64(seed.wrapping_add(first_game + game - 1));
+        let board = network.train_game(learning_rate, &mut rng);
+        let score = GameEngine::get_score(board);
+        total_score += score;
+        best_score = best_score.max(score);
+        if GameEngine::get_highest_tile_val(board) >= 2048 {
+            reached_2048 += 1;
+        }
+
+        if game % report_every == 0 || game == games {
+            let played = (game - 1) % report_every + 1;
+            println!(
+                "Games {}: avg score {:.0}, best {}, 2048 rate {:.1}% ({:.0}s)",
+                game,
+                total_score as f64 / played as f64,
+                best_score,
+                reached_2048 as f64 / played as f64 * 100.0,
+                start_time.elapsed().as_secs_f64()
+            );
+            total_score = 0;
+            best_score = 0;
+            reached_2048 = 0;
+            network.save(&weights_file)?;
+        }
+    }
+
+    if games > 0 {
+        println!("Weights saved to {}", weights_file);
+    }
+    Ok(())
+}
+
+fn print_usage() {
+    println!("Usage: cargo run --release --bin train_ntuple -- [options]");
+    println!("  --games N           Self-play games to train on (default 100000)");
+    println!("  --learning-rate X   Share of the TD(0) error taken off per update (default 0.1)");
+    println!("  --seed N            Seed of the first game's tile spawns, the rest follow on (default 0)");
+    println!("  --report-every N    Games between progress reports and saves (default 1000)");
+    println!("  --weights PATH      Weights file, continued from if present (default ntuple_weights.bin)");
+}
@@ -0,0 +1,309 @@
+use crate::{Frontend, PlayerInput, SpectatorInput, Turn};
+use ai_2048::engine as GameEngine;
+use ai_2048::engine::{Board, Move};
+use ai_2048::timer::GameTimer;
+use crossterm::cursor::{Hide, MoveTo, Show};
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::style::{Color, Print, ResetColor, SetBackgroundColor, SetForegroundColor};
+use crossterm::terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::{execute, queue};
+use std::io::{self, Stdout, Write};
+use std::time::{Duration, Instant};
+
+const CELL_WIDTH: usize = 8;
+const CELL_HEIGHT: usize = 3;
+const BOARD_GAP: usize = 4;
+// How often a running clock is redrawn while waiting for a key
+const CLOCK_REFRESH: Duration = Duration::from_millis(200);
+
+const BOARD_COLOR: Color = rgb(0xbbada0);
+const EMPTY_COLOR: Color = rgb(0xcdc1b4);
+const DARK_TEXT: Color = rgb(0x776e65);
+const LIGHT_TEXT: Color = rgb(0xf9f6f2);
+
+enum KeyWait {
+    Key(KeyEvent),
+    TimeUp,
+    /// `until` passed without a key
+    Elapsed,
+    Failed,
+}
+
+/// The terminal in raw mode on the alternate screen, put back into its normal state when
+/// dropped so that later output goes to the usual screen.
+pub struct Screen {
+    stdout: Stdout,
+}
+
+impl Screen {
+    pub fn new() -> io::Result<Self> {
+        terminal::enable_raw_mode()?;
+        let mut stdout = io::stdout();
+        if let Err(e) = execute!(stdout, EnterAlternateScreen, Hide) {
+            let _ = terminal::disable_raw_mode();
+            return Err(e);
+        }
+        Ok(Screen { stdout })
+    }
+
+    pub fn clear(&mut self) -> io::Result<()> {
+        queue!(self.stdout, MoveTo(0, 0), Clear(ClearType::All))
+    }
+
+    /// Writes `text` on `row` and moves on to the next row.
+    pub fn line(&mut self, row: &mut u16, text: &str) -> io::Result<()> {
+        queue!(self.stdout, MoveTo(0, *row), Print(text))?;
+        *row += 1;
+        Ok(())
+    }
+
+    /// Draws the boards side by side from `row` and moves on to the row below them.
+    pub fn boards(&mut self, row: &mut u16, boards: &[Board]) -> io::Result<()> {
+        let width = 4 * CELL_WIDTH + 5;
+        let stdout = &mut self.stdout;
+        for (idx, &board) in boards.iter().enumerate() {
+            let col = (idx * (width + BOARD_GAP)) as u16;
+            let mut y = *row;
+            let tiles = GameEngine::to_vec(board);
+            for tile_row in tiles.chunks(4) {
+                queue!(stdout, MoveTo(col, y), SetBackgroundColor(BOARD_COLOR), Print(" ".repeat(width)))?;
+                y += 1;
+                for cell_line in 0..CELL_HEIGHT {
+                    queue!(stdout, MoveTo(col, y), SetBackgroundColor(BOARD_COLOR), Print(" "))?;
+                    for &tile in tile_row {
+                        let text = if tile > 0 && cell_line == CELL_HEIGHT / 2 {
+                            (1u32 << tile).to_string()
+                        } else {
+                            String::new()
+                        };
+                        let (background, foreground) = tile_colors(tile);
+                        queue!(
+                            stdout,
+                            SetBackgroundColor(background),
+                            SetForegroundColor(foreground),
+                            Print(format!("{:^width$}", text, width = CELL_WIDTH)),
+                            SetBackgroundColor(BOARD_COLOR),
+                            Print(" ")
+                        )?;
+                    }
+                    queue!(stdout, ResetColor)?;
+                    y += 1;
+                }
+            }
+            queue!(stdout, MoveTo(col, y), SetBackgroundColor(BOARD_COLOR), Print(" ".repeat(width)), ResetColor)?;
+        }
+        *row += (4 * (CELL_HEIGHT + 1) + 1) as u16;
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.stdout.flush()
+    }
+}
+
+impl Drop for Screen {
+    fn drop(&mut self) {
+        let _ = execute!(self.stdout, ResetColor, Show, LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+/// Full-screen client that reads single keypresses and redraws the board in place.
+pub struct TerminalUi {
+    screen: Screen,
+    turn: Option<Turn>,
+    messages: Vec<String>,
+    pending: Vec<String>,
+    clock: Option<String>,
+}
+
+impl TerminalUi {
+    pub fn new() -> io::Result<Self> {
+        Ok(TerminalUi {
+            screen: Screen::new()?,
+            turn: None,
+            messages: Vec::new(),
+            pending: Vec::new(),
+            clock: None,
+        })
+    }
+
+    // Waits for a keypress until `until`, keeping the clock on screen up to date
+    fn next_key(&mut self, timer: &GameTimer, until: Option<Instant>) -> KeyWait {
+        loop {
+            if timer.is_expired() {
+                return KeyWait::TimeUp;
+            }
+            let now = Instant::now();
+            if until.is_some_and(|until| now >= until) {
+                return KeyWait::Elapsed;
+            }
+            let mut wait = match timer.remaining() {
+                Some(remaining) => remaining.min(CLOCK_REFRESH),
+                None if self.clock.is_some() => CLOCK_REFRESH,
+                None => Duration::from_secs(60),
+            };
+            if let Some(until) = until {
+                wait = wait.min(until - now);
+            }
+            match event::poll(wait) {
+                Ok(true) => match event::read() {
+                    Ok(Event::Key(key)) if key.kind == KeyEventKind::Press => return KeyWait::Key(key),
+                    Ok(Event::Resize(_, _)) => {
+                        let _ = self.render();
+                    }
+                    Ok(_) => {}
+                    Err(_) => return KeyWait::Failed,
+                },
+                Ok(false) => {}
+                Err(_) => return KeyWait::Failed,
+            }
+            let clock = timer.display();
+            if clock != self.clock {
+                self.clock = clock;
+                let _ = self.render();
+            }
+        }
+    }
+
+    fn render(&mut self) -> io::Result<()> {
+        let turn = match &self.turn {
+            Some(turn) => turn,
+            None => return Ok(()),
+        };
+        let mut row = 0;
+        let screen = &mut self.screen;
+        screen.clear()?;
+
+        screen.line(&mut row, "=== 2048 Human Player with AI Scoring ===")?;
+        let mut status = turn.status.clone();
+        if let Some(clock) = &self.clock {
+            status.push_str(&format!(" | {}", clock));
+        }
+        screen.line(&mut row, &status)?;
+        screen.line(&mut row, "")?;
+        screen.boards(&mut row, &[turn.board])?;
+        screen.line(&mut row, "")?;
+
+        for text in &turn.analysis {
+            screen.line(&mut row, text)?;
+        }
+        screen.line(&mut row, "")?;
+        for text in &self.messages {
+            screen.line(&mut row, text)?;
+        }
+        screen.line(&mut row, "")?;
+        match &turn.autoplay {
+            Some(autoplay) => {
+                screen.line(&mut row, autoplay)?;
+                screen.line(&mut row, "Space to pause or resume, N to step, T to take over, Q or Esc to quit")?;
+            }
+            None if turn.hints => screen.line(&mut row, "Arrow keys or WASD to move, H for a hint, Q or Esc to quit")?,
+            None => screen.line(&mut row, "Arrow keys or WASD to move, Q or Esc to quit")?,
+        }
+        screen.flush()
+    }
+}
+
+impl Frontend for TerminalUi {
+    fn show(&mut self, turn: &Turn, timer: &GameTimer) {
+        self.turn = Some(turn.clone());
+        self.messages = std::mem::take(&mut self.pending);
+        self.clock = timer.display();
+        let _ = self.render();
+    }
+
+    fn next_input(&mut self, timer: &GameTimer) -> PlayerInput {
+        loop {
+            match self.next_key(timer, None) {
+                KeyWait::Key(key) => {
+                    if let Some(input) = key_input(key) {
+                        return input;
+                    }
+                }
+                KeyWait::TimeUp => return PlayerInput::TimeUp,
+                KeyWait::Elapsed => {}
+                KeyWait::Failed => return PlayerInput::Quit,
+            }
+        }
+    }
+
+    fn spectator_input(&mut self, timer: &GameTimer, wait: Option<Duration>) -> Option<SpectatorInput> {
+        let until = wait.map(|wait| Instant::now() + wait);
+        loop {
+            match self.next_key(timer, until) {
+                KeyWait::Key(key) => {
+                    if let Some(input) = spectator_key_input(key) {
+                        return Some(input);
+                    }
+                }
+                KeyWait::TimeUp => return Some(SpectatorInput::TimeUp),
+                KeyWait::Elapsed => return None,
+                KeyWait::Failed => return Some(SpectatorInput::Quit),
+            }
+        }
+    }
+
+    fn notify(&mut self, message: &str) {
+        self.pending.push(message.to_string());
+    }
+}
+
+pub fn is_interrupt(key: &KeyEvent) -> bool {
+    key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL)
+}
+
+fn key_input(key: KeyEvent) -> Option<PlayerInput> {
+    let input = match key.code {
+        _ if is_interrupt(&key) => PlayerInput::Quit,
+        KeyCode::Up | KeyCode::Char('w') | KeyCode::Char('W') => PlayerInput::Move(Move::Up),
+        KeyCode::Down | KeyCode::Char('s') | KeyCode::Char('S') => PlayerInput::Move(Move::Down),
+        KeyCode::Left | KeyCode::Char('a') | KeyCode::Char('A') => PlayerInput::Move(Move::Left),
+        KeyCode::Right | KeyCode::Char('d') | KeyCode::Char('D') => PlayerInput::Move(Move::Right),
+        KeyCode::Char('h') | KeyCode::Char('H') => PlayerInput::Hint,
+        KeyCode::Char('q') | KeyCode::Char('Q') | KeyCode::Esc => PlayerInput::Quit,
+        _ => return None,
+    };
+    Some(input)
+}
+
+fn spectator_key_input(key: KeyEvent) -> Option<SpectatorInput> {
+    let input = match key.code {
+        _ if is_interrupt(&key) => SpectatorInput::Quit,
+        KeyCode::Char(' ') | KeyCode::Char('p') | KeyCode::Char('P') => SpectatorInput::TogglePause,
+        KeyCode::Char('n') | KeyCode::Char('N') => SpectatorInput::Step,
+        KeyCode::Char('t') | KeyCode::Char('T') => SpectatorInput::TakeOver,
+        KeyCode::Char('q') | KeyCode::Char('Q') | KeyCode::Esc => SpectatorInput::Quit,
+        _ => return None,
+    };
+    Some(input)
+}
+
+// Background and text colors of a tile, matching the web frontend
+fn tile_colors(tile: u8) -> (Color, Color) {
+    let background = match tile {
+        0 => EMPTY_COLOR,
+        1 => rgb(0xeee4da),
+        2 => rgb(0xede0c8),
+        3 => rgb(0xf2b179),
+        4 => rgb(0xf59563),
+        5 => rgb(0xf67c5f),
+        6 => rgb(0xf65e3b),
+        7 => rgb(0xedcf72),
+        8 => rgb(0xedcc61),
+        9 => rgb(0xedc850),
+        10 => rgb(0xedc53f),
+        11 => rgb(0xedc22e),
+        _ => rgb(0x3c3a32),
+    };
+    let foreground = if tile <= 2 { DARK_TEXT } else { LIGHT_TEXT };
+    (background, foreground)
+}
+
+const fn rgb(hex: u32) -> Color {
+    Color::Rgb {
+        r: (hex >> 16) as u8,
+        g: (hex >> 8) as u8,
+        b: hex as u8,
+    }
+}
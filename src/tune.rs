@@ -0,0 +1,332 @@
+use ai_2048::engine as GameEngine;
+use ai_2048::expectimax::{Expectimax, HeuristicWeights};
+use ai_2048::simulation;
+use rand::distributions::{Distribution, Normal};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+const ELITES: usize = 2;
+const TOURNAMENT_SIZE: usize = 3;
+const MUTATION_RATE: f64 = 0.3;
+const MUTATION_SIGMA: f64 = 0.2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+enum Objective {
+    Score,
+    Reach2048,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TuneConfig {
+    objective: Objective,
+    population: usize,
+    games: u64,
+    depth: u64,
+    seed: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Candidate {
+    weights: HeuristicWeights,
+    fitness: f64,
+    average_score: f64,
+    reach_2048_rate: f64,
+    generation: u32,
+}
+
+// Everything needed to carry on tuning after the process is stopped.
+#[derive(Debug, Serialize, Deserialize)]
+struct Checkpoint {
+    config: TuneConfig,
+    generation: u32,
+    population: Vec<HeuristicWeights>,
+    best: Option<Candidate>,
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args: Vec<String> = std::env::args().collect();
+    let mut config = TuneConfig {
+        objective: Objective::Score,
+        population: 16,
+        games: 8,
+        depth: 2,
+        seed: 0,
+    };
+    let mut generations = 20;
+    let mut threads = thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+    let mut checkpoint_file = String::from("tune_checkpoint.json");
+    let mut output_file = String::from("tuned_weights.json");
+
+    // Options that have to agree with a checkpoint being resumed
+    let mut given = Vec::new();
+
+    let mut idx = 1;
+    while idx < args.len() {
+        let value = args.get(idx + 1).ok_or_else(|| format!("Missing value for {}", args[idx]));
+        if ["--population", "--games", "--depth", "--seed", "--objective"].contains(&args[idx].as_str()) {
+            given.push(args[idx].clone());
+        }
+        match args[idx].as_str() {
+            "--generations" => generations = value?.parse()?,
+            "--population" => config.population = value?.parse()?,
+            "--games" => config.games = value?.parse()?,
+            "--depth" => config.depth = value?.parse()?,
+            "--seed" => config.seed = value?.parse()?,
+            "--threads" => threads = value?.parse()?,
+            "--checkpoint" => checkpoint_file = value?.clone(),
+            "--output" => output_file = value?.clone(),
+            "--objective" => {
+                config.objective = match value?.as_str() {
+                    "score" => Objective::Score,
+                    "2048" => Objective::Reach2048,
+                    other => return Err(format!("Unknown objective: {}", other).into()),
+                }
+            }
+            _ => {
+                print_usage();
+                return Ok(());
+            }
+        }
+        idx += 2;
+    }
+
+    if config.population <= ELITES {
+        return Err(format!("Population must be larger than {}", ELITES).into());
+    }
+    if config.games == 0 {
+        return Err("--games must be at least 1".into());
+    }
+
+    GameEngine::new();
+
+    let mut checkpoint = if Path::new(&checkpoint_file).exists() {
+        let checkpoint: Checkpoint = serde_json::from_str(&std::fs::read_to_string(&checkpoint_file)?)?;
+        let differing = differing_options(&config, &checkpoint.config, &given);
+        if !differing.is_empty() {
+            return Err(format!(
+                "{} was made with a different {}; leave the option out to resume or pass another --checkpoint",
+                checkpoint_file,
+                differing.join(", ")
+            )
+            .into());
+        }
+        println!(
+            "Resuming from {} at generation {}",
+            checkpoint_file, checkpoint.generation
+        );
+        checkpoint
+    } else {
+        Checkpoint {
+            population: initial_population(&config),
+            config,
+            generation: 0,
+            best: None,
+        }
+    };
+
+    while checkpoint.generation < generations {
+        let generation = checkpoint.generation;
+        let evaluated = evaluate_population(&checkpoint.config, generation, &checkpoint.population, threads);
+
+        let leader = &evaluated[0];
+        println!(
+            "Generation {}: best fitness {:.4} (avg score {:.0}, 2048 rate {:.1}%)",
+            generation,
+            leader.fitness,
+            leader.average_score,
+            leader.reach_2048_rate * 100.0
+        );
+        let improved = match &checkpoint.best {
+            Some(best) => leader.fitness > best.fitness,
+            None => true,
+        };
+        if improved {
+            checkpoint.best = Some(leader.clone());
+        }
+
+        checkpoint.population = next_population(&checkpoint.config, generation, &evaluated);
+        checkpoint.generation += 1;
+        save_checkpoint(&checkpoint, &checkpoint_file)?;
+    }
+
+    match &checkpoint.best {
+        Some(best) => {
+            std::fs::write(&output_file, serde_json::to_string_pretty(&best.weights)?)?;
+            println!("\nBest weights (generation {}, fitness {:.4}):", best.generation, best.fitness);
+            println!("{:#?}", best.weights);
+            println!("Written to {}", output_file);
+        }
+        None => println!("No generations were run"),
+    }
+
+    Ok(())
+}
+
+fn print_usage() {
+    println!("Usage: cargo run --release --bin tune -- [options]");
+    println!("  --generations N     Generations to run in total (default 20)");
+    println!("  --population N      Candidates per generation (default 16)");
+    println!("  --games N           Games per candidate per generation (default 8)");
+    println!("  --depth N           Search depth cap for self-play (default 2)");
+    println!("  --objective OBJ     'score' (average score) or '2048' (2048 reach rate)");
+    println!("  --seed N            Base seed for games and mutations (default 0)");
+    println!("  --threads N         Worker threads (default: all cores)");
+    println!("  --checkpoint PATH   Population checkpoint, resumed if present (default tune_checkpoint.json);");
+    println!("                      the options above must match the run that made it");
+    println!("  --output PATH       Where to write the best weights (default tuned_weights.json)");
+}
+
+// The options given on the command line that a resumed run would not honour
+fn differing_options(config: &TuneConfig, resumed: &TuneConfig, given: &[String]) -> Vec<String> {
+    given
+        .iter()
+        .filter(|option| match option.as_str() {
+            "--population" => config.population != resumed.population,
+            "--games" => config.games != resumed.games,
+            "--depth" => config.depth != resumed.depth,
+            "--seed" => config.seed != resumed.seed,
+            "--objective" => config.objective != resumed.objective,
+            _ => false,
+        })
+        .cloned()
+        .collect()
+}
+
+fn initial_population(config: &TuneConfig) -> Vec<HeuristicWeights> {
+    let mut rng = StdRng::seed_from_u64(config.seed);
+    let defaults = HeuristicWeights::default();
+    let mut population = vec![defaults];
+    while population.len() < config.population {
+        population.push(mutate(defaults, 1.0, &mut rng));
+    }
+    population
+}
+
+// Plays every candidate on the same seeds so they are compared on equal luck.
+// Returns the candidates sorted best first.
+fn evaluate_population(
+    config: &TuneConfig,
+    generation: u32,
+    population: &[HeuristicWeights],
+    threads: usize,
+) -> Vec<Candidate> {
+    let first_seed = config
+        .seed
+        .wrapping_mul(1_000_003)
+        .wrapping_add(generation as u64 * config.games);
+    let next_idx = AtomicUsize::new(0);
+    let results = Mutex::new(Vec::with_capacity(population.len()));
+
+    thread::scope(|scope| {
+        for _ in 0..threads.max(1) {
+            scope.spawn(|| loop {
+                let idx = next_idx.fetch_add(1, Ordering::SeqCst);
+                if idx >= population.len() {
+                    break;
+                }
+                let candidate = evaluate_candidate(config, generation, population[idx], first_seed);
+                results.lock().unwrap().push((idx, candidate));
+            });
+        }
+    });
+
+    // Break ties by population order so a resumed run makes the same choices
+    let mut results = results.into_inner().unwrap();
+    results.sort_by(|(idx_a, a), (idx_b, b)| {
+        b.fitness.total_cmp(&a.fitness).then(idx_a.cmp(idx_b))
+    });
+    results.into_iter().map(|(_, candidate)| candidate).collect()
+}
+
+fn evaluate_candidate(
+    config: &TuneConfig,
+    generation: u32,
+    weights: HeuristicWeights,
+    first_seed: u64,
+) -> Candidate {
+    let mut expectimax = Expectimax::with_weights(weights).with_max_depth(config.depth);
+    let mut total_score = 0;
+    let mut reached_2048 = 0;
+    for game in 0..config.games {
+        let result = simulation::play_game(&mut expectimax, first_seed.wrapping_add(game));
+        total_score += result.score;
+        if result.highest_tile >= 2048 {
+            reached_2048 += 1;
+        }
+    }
+
+    let average_score = total_score as f64 / config.games as f64;
+    let reach_2048_rate = reached_2048 as f64 / config.games as f64;
+    let fitness = match config.objective {
+        Objective::Score => average_score,
+        Objective::Reach2048 => reach_2048_rate,
+    };
+    Candidate {
+        weights,
+        fitness,
+        average_score,
+        reach_2048_rate,
+        generation,
+    }
+}
+
+fn next_population(config: &TuneConfig, generation: u32, ranked: &[Candidate]) -> Vec<HeuristicWeights> {
+    let mut rng = StdRng::seed_from_u64(config.seed ^ ((generation as u64 + 1) << 32));
+    let mut population: Vec<HeuristicWeights> = ranked.iter().take(ELITES).map(|c| c.weights).collect();
+    while population.len() < config.population {
+        let parent_a = tournament(ranked, &mut rng);
+        let parent_b = tournament(ranked, &mut rng);
+        let child = crossover(parent_a, parent_b, &mut rng);
+        population.push(mutate(child, MUTATION_RATE, &mut rng));
+    }
+    population
+}
+
+fn tournament<R: Rng>(ranked: &[Candidate], rng: &mut R) -> HeuristicWeights {
+    // The candidates are sorted, so the lowest index drawn is the fittest
+    let winner = (0..TOURNAMENT_SIZE)
+        .map(|_| rng.gen_range(0, ranked.len()))
+        .min()
+        .unwrap();
+    ranked[winner].weights
+}
+
+fn crossover<R: Rng>(a: HeuristicWeights, b: HeuristicWeights, rng: &mut R) -> HeuristicWeights {
+    let (a, b) = (a.to_array(), b.to_array());
+    let mut child = a;
+    for (gene, &other) in child.iter_mut().zip(b.iter()) {
+        if rng.gen_bool(0.5) {
+            *gene = other;
+        }
+    }
+    HeuristicWeights::from_array(child)
+}
+
+// Mutates multiplicatively so every weight stays positive and changes relative to its scale.
+fn mutate<R: Rng>(weights: HeuristicWeights, rate: f64, rng: &mut R) -> HeuristicWeights {
+    let normal = Normal::new(0.0, MUTATION_SIGMA);
+    let mut genes = weights.to_array();
+    for gene in genes.iter_mut() {
+        if rng.gen_bool(rate) {
+            *gene *= normal.sample(rng).exp();
+        }
+    }
+    let mut weights = HeuristicWeights::from_array(genes);
+    weights.sum_power = weights.sum_power.clamp(1.0, 6.0);
+    weights.monotonicity_power = weights.monotonicity_power.clamp(1.0, 6.0);
+    weights
+}
+
+fn save_checkpoint(checkpoint: &Checkpoint, filename: &str) -> Result<(), Box<dyn Error>> {
+    // Write then rename so an interrupted save never leaves a truncated checkpoint
+    let tmp_filename = format!("{}.tmp", filename);
+    std::fs::write(&tmp_filename, serde_json::to_string_pretty(checkpoint)?)?;
+    std::fs::rename(&tmp_filename, filename)?;
+    Ok(())
+}
@@ -0,0 +1,343 @@
+//! Infers the tile that appeared after every recorded move and checks that each recorded board
+//! follows from the one before it.
+//!
+//! Boards are recorded before each move, so the board after move `n` is `shift(board_n, move_n)`
+//! plus one new 2 or 4 on an empty cell, and that must be the board recorded for move `n + 1`.
+//! The last move of a game has no following board, so its spawn stays unknown.
+
+use crate::engine as GameEngine;
+use crate::engine::{Board, Move, MOVES};
+use crate::schema::GameMove;
+use serde::Serialize;
+use std::fmt;
+
+/// A tile that appeared after a move.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Spawn {
+    /// Cell index, row by row from the top left
+    pub index: usize,
+    /// Tile exponent: 1 for a 2, 2 for a 4
+    pub tile: u8,
+}
+
+impl Spawn {
+    pub fn place(&self, board: Board) -> Board {
+        board | (self.tile as Board) << ((15 - self.index) * 4)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TransitionError {
+    /// The recorded move leaves the board as it was
+    NoEffect,
+    /// The next board is not the moved board plus one new tile
+    NotASpawn {
+        /// Cells that differ from the moved board
+        changed_cells: usize,
+    },
+    /// One tile appeared, but not a 2 or a 4
+    BadSpawnValue(u8),
+}
+
+impl fmt::Display for TransitionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TransitionError::NoEffect => write!(f, "the move does not change the board"),
+            TransitionError::NotASpawn { changed_cells } => {
+                write!(f, "{} cells differ from the moved board instead of one new tile", changed_cells)
+            }
+            TransitionError::BadSpawnValue(tile) => write!(f, "a {} appeared, not a 2 or a 4", 1u32 << tile),
+        }
+    }
+}
+
+/// The tile that turns `board` after `direction` into `next`.
+pub fn reconstruct_spawn(board: Board, direction: Move, next: Board) -> Result<Spawn, TransitionError> {
+    let moved = GameEngine::shift(board, direction);
+    if moved == board {
+        return Err(TransitionError::NoEffect);
+    }
+    let moved_tiles = GameEngine::to_vec(moved);
+    let next_tiles = GameEngine::to_vec(next);
+    let changed: Vec<usize> = (0..16).filter(|&idx| moved_tiles[idx] != next_tiles[idx]).collect();
+    match changed.as_slice() {
+        &[index] if moved_tiles[index] == 0 => match next_tiles[index] {
+            tile @ 1..=2 => Ok(Spawn { index, tile }),
+            tile => Err(TransitionError::BadSpawnValue(tile)),
+        },
+        _ => Err(TransitionError::NotASpawn {
+            changed_cells: changed.len(),
+        }),
+    }
+}
+
+/// Something wrong with a recorded game.
+#[derive(Debug, Clone, PartialEq)]
+pub enum IssueKind {
+    MalformedBoard,
+    /// Move numbers skip from `expected` to `found`
+    MissingMoves { expected: u32, found: u32 },
+    /// The next board cannot follow from this move; `explained_by` lists the directions that
+    /// would explain it, which points at a wrong direction mapping rather than bad boards
+    Impossible {
+        error: TransitionError,
+        explained_by: Vec<Move>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Issue {
+    pub session_id: String,
+    pub move_number: u32,
+    pub kind: IssueKind,
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} move {}: ", self.session_id, self.move_number)?;
+        match &self.kind {
+            IssueKind::MalformedBoard => write!(f, "malformed board"),
+            IssueKind::MissingMoves { expected, found } => {
+                write!(f, "expected move {} but found move {}", expected, found)
+            }
+            IssueKind::Impossible { error, explained_by } => {
+                write!(f, "impossible transition, {}", error)?;
+                if !explained_by.is_empty() {
+                    let directions: Vec<String> = explained_by.iter().map(|m| format!("{:?}", m)).collect();
+                    write!(f, " (consistent with {} instead)", directions.join(" or "))?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// The tile that appeared after a recorded move.
+#[derive(Debug, Clone, Serialize)]
+pub struct SpawnRecord {
+    pub session_id: String,
+    pub move_number: u32,
+    pub direction: Move,
+    pub index: usize,
+    pub row: usize,
+    pub col: usize,
+    /// Tile value, 2 or 4
+    pub value: u32,
+}
+
+/// A recorded move and the tile that appeared after it.
+#[derive(Debug, Clone, Copy)]
+pub struct SpawnStep {
+    pub move_number: u32,
+    pub direction: Move,
+    /// `None` where the transition was impossible, and always for the last move
+    pub spawn: Option<Spawn>,
+}
+
+/// A recorded game as its first board and the spawn after every move but the last, enough to
+/// replay it without randomness.
+#[derive(Debug, Clone)]
+pub struct SpawnSequence {
+    pub session_id: String,
+    pub initial: Board,
+    pub steps: Vec<SpawnStep>,
+}
+
+impl SpawnSequence {
+    /// Plays the game back, returning the board before every move and the final board. Stops
+    /// at the first move whose spawn is unknown, apart from the last.
+    pub fn replay(&self) -> Vec<Board> {
+        let mut boards = vec![self.initial];
+        let mut board = self.initial;
+        for (idx, step) in self.steps.iter().enumerate() {
+            board = GameEngine::shift(board, step.direction);
+            match step.spawn {
+                Some(spawn) => board = spawn.place(board),
+                None if idx + 1 == self.steps.len() => {}
+                None => break,
+            }
+            boards.push(board);
+        }
+        boards
+    }
+
+    pub fn records(&self) -> Vec<SpawnRecord> {
+        self.steps
+            .iter()
+            .filter_map(|step| {
+                step.spawn.map(|spawn| SpawnRecord {
+                    session_id: self.session_id.clone(),
+                    move_number: step.move_number,
+                    direction: step.direction,
+                    index: spawn.index,
+                    row: spawn.index / 4,
+                    col: spawn.index % 4,
+                    value: 1 << spawn.tile,
+                })
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ValidationReport {
+    /// Games that can be replayed, which leaves out those whose first board is malformed
+    pub games: Vec<SpawnSequence>,
+    pub issues: Vec<Issue>,
+}
+
+impl ValidationReport {
+    pub fn is_valid(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    pub fn spawn_records(&self) -> Vec<SpawnRecord> {
+        self.games.iter().flat_map(SpawnSequence::records).collect()
+    }
+}
+
+/// Reconstructs the spawns of every game in `moves`, taken session by session in the order
+/// they appear, and reports each transition that cannot have happened.
+pub fn validate_moves(moves: &[GameMove]) -> ValidationReport {
+    let mut report = ValidationReport::default();
+    let mut sessions: Vec<(&str, Vec<&GameMove>)> = Vec::new();
+    for mv in moves {
+        match sessions.iter_mut().find(|(session_id, _)| *session_id == mv.session_id) {
+            Some((_, session)) => session.push(mv),
+            None => sessions.push((&mv.session_id, vec![mv])),
+        }
+    }
+
+    for (session_id, session) in sessions {
+        let issue = |move_number, kind| Issue {
+            session_id: session_id.to_string(),
+            move_number,
+            kind,
+        };
+        let boards: Vec<Option<Board>> = session.iter().map(|mv| mv.board()).collect();
+        for (mv, board) in session.iter().zip(boards.iter()) {
+            if board.is_none() {
+                report.issues.push(issue(mv.move_number, IssueKind::MalformedBoard));
+            }
+        }
+        for pair in session.windows(2) {
+            if pair[1].move_number != pair[0].move_number + 1 {
+                report.issues.push(issue(
+                    pair[1].move_number,
+                    IssueKind::MissingMoves {
+                        expected: pair[0].move_number + 1,
+                        found: pair[1].move_number,
+                    },
+                ));
+            }
+        }
+
+        let mut spawns = Vec::with_capacity(session.len());
+        for (idx, mv) in session.iter().enumerate() {
+            let (board, next) = match (boards[idx], boards.get(idx + 1).copied().flatten()) {
+                (Some(board), Some(next)) => (board, next),
+                _ => {
+                    spawns.push(None);
+                    continue;
+                }
+            };
+            match reconstruct_spawn(board, mv.direction, next) {
+                Ok(spawn) => spawns.push(Some(spawn)),
+                Err(error) => {
+                    let explained_by = MOVES
+                        .iter()
+                        .copied()
+                        .filter(|&other| other != mv.direction && reconstruct_spawn(board, other, next).is_ok())
+                        .collect();
+                    report
+                        .issues
+                        .push(issue(mv.move_number, IssueKind::Impossible { error, explained_by }));
+                    spawns.push(None);
+                }
+            }
+        }
+
+        // Without its first board a game cannot be replayed; the malformed board is reported
+        let initial = match boards[0] {
+            Some(initial) => initial,
+            None => continue,
+        };
+        report.games.push(SpawnSequence {
+            session_id: session_id.to_string(),
+            initial,
+            steps: session
+                .iter()
+                .zip(spawns)
+                .map(|(mv, spawn)| SpawnStep {
+                    move_number: mv.move_number,
+                    direction: mv.direction,
+                    spawn,
+                })
+                .collect(),
+        });
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema;
+
+    #[test]
+    fn it_reconstructs_spawns() {
+        GameEngine::new();
+        // 2 2 . . on the top row; Left merges them into a 4
+        let board = 0x1100_0000_0000_0000;
+        let spawn = reconstruct_spawn(board, Move::Left, 0x2000_0000_0000_0001).unwrap();
+        assert_eq!(spawn, Spawn { index: 15, tile: 1 });
+        assert_eq!(spawn.place(0x2000_0000_0000_0000), 0x2000_0000_0000_0001);
+
+        assert_eq!(reconstruct_spawn(board, Move::Up, 0x1100_0000_0000_0001), Err(TransitionError::NoEffect));
+        assert_eq!(
+            reconstruct_spawn(board, Move::Left, 0x2000_0000_0000_0003),
+            Err(TransitionError::BadSpawnValue(3))
+        );
+        assert_eq!(
+            reconstruct_spawn(board, Move::Left, 0x2100_0000_0000_0001),
+            Err(TransitionError::NotASpawn { changed_cells: 2 })
+        );
+    }
+
+    #[test]
+    fn it_validates_and_replays_recorded_games() {
+        GameEngine::new();
+        let mut moves = schema::read_moves("deploy/data/game_moves_20250801_162156_oej58.csv").unwrap();
+        let report = validate_moves(&moves);
+        assert!(report.is_valid(), "{:?}", report.issues);
+        assert_eq!(report.spawn_records().len(), moves.len() - 1);
+
+        // The spawns replay the recorded boards exactly
+        let game = &report.games[0];
+        let replayed = game.replay();
+        for (mv, board) in moves.iter().zip(replayed.iter()) {
+            assert_eq!(mv.board(), Some(*board));
+        }
+
+        // A swapped direction is caught, along with the one that fits
+        let board = moves[5].board().unwrap();
+        let original = moves[5].direction;
+        moves[5].direction = MOVES
+            .iter()
+            .copied()
+            .find(|&m| m != original && GameEngine::shift(board, m) != board)
+            .unwrap();
+        let report = validate_moves(&moves);
+        assert_eq!(report.issues.len(), 1);
+        match &report.issues[0].kind {
+            IssueKind::Impossible { explained_by, .. } => assert!(explained_by.contains(&original)),
+            other => panic!("unexpected issue {:?}", other),
+        }
+
+        // A game whose first board is malformed is reported but not replayed
+        moves[0].board_state = String::from("0,0,0");
+        let report = validate_moves(&moves[..5]);
+        assert_eq!(report.issues[0].kind, IssueKind::MalformedBoard);
+        assert!(report.games.is_empty());
+    }
+}
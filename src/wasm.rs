@@ -1,43 +1,75 @@
+use crate::agent::Agent;
 use crate::expectimax::Expectimax;
 use crate::engine as GameEngine;
-use crate::engine::{Move, Board};
+use crate::engine::{Move, Board, MOVES};
+use crate::monte_carlo::MonteCarlo;
 use wasm_bindgen::prelude::*;
 
 #[wasm_bindgen]
-pub struct WasmExpectimax;
+pub struct WasmExpectimax(Expectimax);
+
+impl Default for WasmExpectimax {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 #[wasm_bindgen]
 impl WasmExpectimax {
     pub fn new() -> Self {
         GameEngine::new();
-        Expectimax::new();
-        WasmExpectimax
-    }
-
-    //pub fn get_next_move(&self, board: Board) -> i32 {
-    //    match Expectimax.get_next_move(board) {
-    //        Some(direction) => match direction {
-    //            Move::Up => 0,
-    //            Move::Right => 1,
-    //            Move::Down => 2,
-    //            Move::Left => 3,
-    //        },
-    //        None => self.get_possible_move(board),
-    //    }
-    //}
+        WasmExpectimax(Expectimax::new())
+    }
+
+    pub fn get_next_move(&mut self, board: Board) -> i32 {
+        match self.0.choose(board) {
+            Some(direction) => to_js_direction(direction),
+            None => self.get_possible_move(board),
+        }
+    }
 
     fn get_possible_move(&self, board: Board) -> i32 {
-        for &direction in &[Move::Up, Move::Down, Move::Left, Move::Right] {
+        for &direction in &MOVES {
             let new_board = GameEngine::shift(board, direction);
             if new_board != board {
-                match direction {
-                    Move::Up => return 0,
-                    Move::Right => return 1,
-                    Move::Down => return 2,
-                    Move::Left => return 3,
-                }
+                return to_js_direction(direction);
             }
         }
-        return -1;
+        -1
+    }
+}
+
+/// Depth-free move scoring by random rollouts, cheap enough to run on every move in the browser.
+#[wasm_bindgen]
+pub struct WasmMonteCarlo(MonteCarlo);
+
+#[wasm_bindgen]
+impl WasmMonteCarlo {
+    pub fn new(rollouts: u32) -> Self {
+        GameEngine::new();
+        WasmMonteCarlo(MonteCarlo::new(rollouts))
+    }
+
+    /// Average rollout score for [Up, Right, Down, Left], -1 for moves that do nothing.
+    pub fn score_moves(&mut self, board: Board) -> Vec<f64> {
+        let [up, down, left, right] = self.0.score_moves(board);
+        [up, right, down, left]
+            .iter()
+            .map(|score| score.unwrap_or(-1.))
+            .collect()
+    }
+
+    pub fn get_next_move(&mut self, board: Board) -> i32 {
+        self.0.choose(board).map_or(-1, to_js_direction)
+    }
+}
+
+// The web game numbers directions clockwise from Up
+fn to_js_direction(direction: Move) -> i32 {
+    match direction {
+        Move::Up => 0,
+        Move::Right => 1,
+        Move::Down => 2,
+        Move::Left => 3,
     }
 }
@@ -0,0 +1,218 @@
+use crate::agent::{best_move, Agent};
+use crate::engine as GameEngine;
+use crate::engine::{Board, Move, MOVES};
+use crate::move_quality::{self, MoveGrade, QualityThresholds};
+use crate::player_data::SessionRecord;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::path::Path;
+
+/// A move recorded by the web frontend, read from either `game_moves_*.csv` (by header) or the
+/// `moves` of `game_session_*.json` (by camelCase key).
+#[derive(Debug, Clone, Deserialize)]
+pub struct WebMoveRecord {
+    #[serde(rename = "Session ID", alias = "sessionId")]
+    pub session_id: String,
+    #[serde(rename = "Player Name", alias = "playerName")]
+    pub player_name: String,
+    #[serde(rename = "Game Mode", alias = "gameMode")]
+    pub game_mode: String,
+    #[serde(rename = "Move Number", alias = "moveNumber")]
+    pub move_number: u32,
+    #[serde(rename = "Timestamp", alias = "timestamp")]
+    pub timestamp: String,
+    #[serde(rename = "Direction", alias = "direction")]
+    pub direction: String,
+    /// Game score after the move
+    #[serde(rename = "Score", alias = "score")]
+    pub score: u64,
+    /// Tile values before the move, column by column
+    #[serde(rename = "Board State", alias = "boardState")]
+    pub board_state: String,
+    #[serde(rename = "Move Time", alias = "moveTime")]
+    pub move_time: u64,
+    #[serde(rename = "Tiles Added", alias = "tilesAdded", default)]
+    pub tiles_added: Option<u32>,
+    #[serde(rename = "Tiles Removed", alias = "tilesRemoved", default)]
+    pub tiles_removed: Option<u32>,
+    /// Only written by the server; the client's own CSV export has no such column
+    #[serde(rename = "Is Bad Move", alias = "isBadMove", default)]
+    pub is_bad_move: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct WebSession {
+    moves: Vec<WebMoveRecord>,
+}
+
+/// A web move scored again by a Rust agent. Scores are in `[Up, Down, Left, Right]` order and
+/// empty for invalid moves.
+#[derive(Debug, Clone, Serialize)]
+pub struct RescoredMove {
+    pub session_id: String,
+    pub player_name: String,
+    pub game_mode: String,
+    pub move_number: u32,
+    pub timestamp: String,
+    pub direction: String,
+    pub score: u64,
+    /// Tile exponents before the move, row by row as in `player_moves_*.csv`
+    pub board_state: String,
+    pub move_time: u64,
+    pub up_score: Option<f64>,
+    pub down_score: Option<f64>,
+    pub left_score: Option<f64>,
+    pub right_score: Option<f64>,
+    pub best_move: String,
+    pub chosen_score: f64,
+    /// Evaluation given up, relative to the best
+    pub score_loss: f64,
+    pub move_grade: MoveGrade,
+    /// The flag from the simplified JavaScript search
+    pub web_bad_move: bool,
+    /// Whether the move grades as a mistake or a blunder
+    pub is_bad_move: bool,
+}
+
+/// Reads the moves of a web `game_moves_*.csv` or `game_session_*.json` file.
+pub fn load_web_moves(filename: &str) -> Result<Vec<WebMoveRecord>, Box<dyn Error>> {
+    if Path::new(filename).extension().is_some_and(|ext| ext == "json") {
+        let session: WebSession = serde_json::from_str(&std::fs::read_to_string(filename)?)?;
+        return Ok(session.moves);
+    }
+    let mut reader = csv::Reader::from_path(filename)?;
+    let mut moves = Vec::new();
+    for result in reader.deserialize() {
+        moves.push(result?);
+    }
+    Ok(moves)
+}
+
+/// Reads the session summary at the top of a web `game_session_*.json` file.
+pub fn load_web_session(filename: &str) -> Result<SessionRecord, Box<dyn Error>> {
+    Ok(serde_json::from_str(&std::fs::read_to_string(filename)?)?)
+}
+
+/// Parses a web board: 16 tile values (not exponents) stored column by column.
+pub fn parse_web_board(board_state: &str) -> Option<Board> {
+    let values: Vec<u64> = board_state
+        .split(',')
+        .map(|value| value.trim().parse().ok())
+        .collect::<Option<_>>()?;
+    if values.len() != 16 {
+        return None;
+    }
+    let mut tiles = [0; 16];
+    for (idx, &value) in values.iter().enumerate() {
+        let exponent = match value {
+            0 => 0,
+            value if value.is_power_of_two() && value <= 1 << GameEngine::MAX_TILE_EXPONENT => {
+                value.trailing_zeros() as u8
+            }
+            _ => return None,
+        };
+        // boardArray[x * 4 + y] holds column x, row y
+        let (col, row) = (idx / 4, idx % 4);
+        tiles[row * 4 + col] = exponent;
+    }
+    Some(GameEngine::from_vec(&tiles))
+}
+
+pub fn rescore_moves(moves: &[WebMoveRecord], ai: &mut dyn Agent) -> Result<Vec<RescoredMove>, String> {
+    rescore_moves_with(moves, ai, &QualityThresholds::for_scores())
+}
+
+/// Scores every recorded position with `ai` and grades the move played by those scores with
+/// `move_quality::grade_by_scores`, flagging mistakes and blunders.
+pub fn rescore_moves_with(
+    moves: &[WebMoveRecord],
+    ai: &mut dyn Agent,
+    thresholds: &QualityThresholds,
+) -> Result<Vec<RescoredMove>, String> {
+    let mut rescored = Vec::with_capacity(moves.len());
+    for record in moves {
+        let board = parse_web_board(&record.board_state)
+            .ok_or_else(|| format!("Move {}: malformed board '{}'", record.move_number, record.board_state))?;
+        let played = crate::player_data::parse_move(&record.direction)
+            .ok_or_else(|| format!("Move {}: unknown direction '{}'", record.move_number, record.direction))?;
+
+        let scores = ai.score_moves(board);
+        let played_idx = MOVES.iter().position(|&direction| direction == played).unwrap();
+        let chosen_score = scores[played_idx]
+            .ok_or_else(|| format!("Move {}: {:?} does not change the board", record.move_number, played))?;
+        let best_score = scores.iter().flatten().fold(f64::NEG_INFINITY, |best, &s| best.max(s));
+        let score_loss = (best_score - chosen_score) / best_score.abs().max(f64::EPSILON);
+        let move_grade = move_quality::grade_by_scores(&scores, played, thresholds)
+            .expect("The move played is valid");
+
+        rescored.push(RescoredMove {
+            session_id: record.session_id.clone(),
+            player_name: record.player_name.clone(),
+            game_mode: record.game_mode.clone(),
+            move_number: record.move_number,
+            timestamp: record.timestamp.clone(),
+            direction: record.direction.clone(),
+            score: record.score,
+            board_state: crate::player_data::board_to_string(board),
+            move_time: record.move_time,
+            up_score: scores[0],
+            down_score: scores[1],
+            left_score: scores[2],
+            right_score: scores[3],
+            best_move: format!("{:?}", best_move(&scores).unwrap_or(Move::Up)),
+            chosen_score,
+            score_loss,
+            move_grade,
+            web_bad_move: record.is_bad_move,
+            is_bad_move: move_grade >= MoveGrade::Mistake,
+        });
+    }
+    Ok(rescored)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::GreedyAgent;
+
+    #[test]
+    fn it_parses_column_major_values() {
+        // A 2 in column 1 row 1, a 4 in column 2 row 2 and a 2048 in the bottom left
+        let board = parse_web_board("0,0,0,2048,0,2,0,0,0,0,4,0,0,0,0,0").unwrap();
+        assert_eq!(board, 0x0000_0100_0020_b000);
+        assert!(parse_web_board("0,0,3,0,0,0,0,0,0,0,0,0,0,0,0,0").is_none());
+        assert!(parse_web_board("0,0,0").is_none());
+        assert!(parse_web_board("32768,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0").is_none());
+    }
+
+    #[test]
+    fn it_rescores_recorded_sessions() {
+        GameEngine::new();
+        let moves = load_web_moves("deploy/data/game_moves_20250801_162156_oej58.csv").unwrap();
+        let json_moves = load_web_moves("deploy/data/game_session_20250801_162156_oej58.json").unwrap();
+        assert_eq!(moves.len(), json_moves.len());
+        assert_eq!(moves[0].board_state, json_moves[0].board_state);
+
+        let rescored = rescore_moves(&moves[..20], &mut GreedyAgent).unwrap();
+        assert_eq!(rescored.len(), 20);
+        assert!(rescored.iter().all(|m| m.score_loss >= 0.));
+        assert!(rescored.iter().all(|m| m.is_bad_move == (m.move_grade >= MoveGrade::Mistake)));
+        assert_eq!(rescored[0].board_state, "0,0,0,0,0,1,0,0,0,0,1,0,0,0,0,0");
+    }
+
+    #[test]
+    fn it_loads_client_exports_without_bad_moves() {
+        // The header written by deploy/js/data_logger.js
+        let csv = "Session ID,Player Name,Game Mode,Move Number,Timestamp,Direction,Score,Board State,Move Time,Tiles Added,Tiles Removed\n\
+                   20250801_162156_oej58,Simon,countup,1,2025-08-01T04:21:59.036Z,Down,0,\"0,0,0,0,0,2,0,0,0,0,2,0,0,0,0,0\",2481,1,0\n";
+        let filename = std::env::temp_dir().join(format!("ai_2048_client_export_{}.csv", std::process::id()));
+        std::fs::write(&filename, csv).unwrap();
+        let moves = load_web_moves(&filename.to_string_lossy()).unwrap();
+        std::fs::remove_file(&filename).unwrap();
+
+        assert_eq!(moves.len(), 1);
+        assert_eq!((moves[0].direction.as_str(), moves[0].tiles_added), ("Down", Some(1)));
+        assert!(!moves[0].is_bad_move);
+    }
+}